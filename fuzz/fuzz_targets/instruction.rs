@@ -0,0 +1,29 @@
+//! Decode arbitrary instruction data against an arbitrary number of
+//! account keys; anything that unpacks must pack back to itself.
+
+#![no_main]
+
+use aex402_sdk::constants::PROGRAM_ID;
+use aex402_sdk::decoder;
+use aex402_sdk::instruction::AeX402Instruction;
+use libfuzzer_sys::fuzz_target;
+use solana_program::pubkey::Pubkey;
+
+fuzz_target!(|input: (u8, &[u8])| {
+    let (n_keys, data) = input;
+
+    if let Ok(ix) = AeX402Instruction::unpack(data) {
+        assert_eq!(AeX402Instruction::unpack(&ix.pack()), Ok(ix));
+    }
+
+    let keys: Vec<Pubkey> = (0..n_keys as usize % 32)
+        .map(|i| Pubkey::new_from_array([i as u8; 32]))
+        .collect();
+
+    if let Ok(decoded) = decoder::decode_instruction(&PROGRAM_ID, &keys, data) {
+        assert_eq!(
+            decoded.accounts.len() + decoded.remaining_accounts.len(),
+            keys.len()
+        );
+    }
+});
@@ -0,0 +1,31 @@
+//! Run the invariant solvers and swap quotes on arbitrary balances, amps
+//! and fees. Failures must come back as `Err`, never as a panic.
+
+#![no_main]
+
+use aex402_sdk::math;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|input: (u64, u64, u64, u64, u64, Vec<u64>)| {
+    let (bal_in, bal_out, amount_in, amp, fee_bps, balances) = input;
+
+    if let Ok(d) = math::try_calc_d(bal_in, bal_out, amp) {
+        assert_eq!(math::calc_d_wide(bal_in, bal_out, amp), Ok(d as u128));
+        if let Some(x_new) = bal_in.checked_add(amount_in) {
+            let _ = math::try_calc_y(x_new, d, amp);
+        }
+    }
+
+    if let Ok(out) = math::try_simulate_swap(bal_in, bal_out, amount_in, amp, fee_bps) {
+        assert!(out <= bal_out);
+        assert_eq!(math::simulate_swap(bal_in, bal_out, amount_in, amp, fee_bps), Some(out));
+    }
+
+    let _ = math::try_calc_d_n(&balances, amp);
+    if balances.len() >= 2 {
+        let (i, j) = (amount_in as usize % balances.len(), fee_bps as usize % balances.len());
+        if let Ok(out) = math::try_simulate_swap_n(&balances, i, j, amount_in, amp, fee_bps) {
+            assert!(out <= balances[j]);
+        }
+    }
+});
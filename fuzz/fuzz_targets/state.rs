@@ -0,0 +1,74 @@
+//! Feed arbitrary account data to every `state` parser, then exercise the
+//! accessors on whatever parses.
+
+#![no_main]
+
+use aex402_sdk::math::PRICE_PRECISION;
+use aex402_sdk::state::{
+    Farm, Lottery, LotteryEntry, NPool, Pool, Registry, TwapResult, UserFarm,
+};
+use borsh::BorshDeserialize;
+use libfuzzer_sys::fuzz_target;
+use solana_program::pubkey::Pubkey;
+
+/// Parse the way services do (exact length) and the way account buffers
+/// are read (prefix of a larger allocation)
+fn parse<T: BorshDeserialize>(data: &[u8]) -> Option<T> {
+    let _ = T::try_from_slice(data);
+    T::deserialize(&mut &data[..]).ok()
+}
+
+fuzz_target!(|input: (i64, u64, &[u8])| {
+    let (now, word, data) = input;
+
+    if let Some(pool) = parse::<Pool>(data) {
+        pool.is_valid();
+        pool.is_paused();
+        pool.get_amp(now);
+        pool.admin_fee_report(now);
+        pool.rated_balances([PRICE_PRECISION; 2]);
+        for candle in pool.hourly_candles.iter().chain(&pool.daily_candles) {
+            candle.high();
+            candle.low();
+            candle.close();
+        }
+    }
+
+    if let Some(pool) = parse::<NPool>(data) {
+        pool.is_valid();
+        pool.is_paused();
+        pool.get_amp(now);
+        pool.accrued_admin_fees();
+        pool.rated_balances(&vec![PRICE_PRECISION; pool.n_tokens as usize]);
+    }
+
+    if let Some(farm) = parse::<Farm>(data) {
+        farm.is_valid();
+    }
+
+    if let Some(user_farm) = parse::<UserFarm>(data) {
+        user_farm.is_valid();
+    }
+
+    if let Some(entry) = parse::<LotteryEntry>(data) {
+        entry.is_valid();
+        entry.is_claimed();
+        entry.is_winner(word);
+    }
+
+    if let Some(lottery) = parse::<Lottery>(data) {
+        lottery.is_valid();
+        if let Some(entry) = parse::<LotteryEntry>(data) {
+            lottery.find_winning_entry(&[(Pubkey::default(), entry)]);
+        }
+    }
+
+    if let Some(registry) = parse::<Registry>(data) {
+        registry.is_valid();
+        registry.contains(&Pubkey::default());
+    }
+
+    let twap = TwapResult::decode(word);
+    twap.price_f64();
+    twap.confidence_pct();
+});
@@ -3,28 +3,36 @@
 use solana_program::pubkey::Pubkey;
 
 /// Program ID
-pub const PROGRAM_ID: Pubkey = solana_program::pubkey!("3AMM53MsJZy2Jvf7PeHHga3bsGjWV4TSaYz29WUtcdje");
+pub const PROGRAM_ID: Pubkey =
+    solana_program::pubkey!("3AMM53MsJZy2Jvf7PeHHga3bsGjWV4TSaYz29WUtcdje");
 
 /// Token Program ID
-pub const TOKEN_PROGRAM_ID: Pubkey = solana_program::pubkey!("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
+pub const TOKEN_PROGRAM_ID: Pubkey =
+    solana_program::pubkey!("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
 
 /// Token-2022 Program ID
-pub const TOKEN_2022_PROGRAM_ID: Pubkey = solana_program::pubkey!("TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb");
+pub const TOKEN_2022_PROGRAM_ID: Pubkey =
+    solana_program::pubkey!("TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb");
 
 // Pool parameters
 pub const MIN_AMP: u64 = 1;
 pub const MAX_AMP: u64 = 100_000;
 pub const DEFAULT_FEE_BPS: u64 = 30;
+pub const MAX_FEE_BPS: u64 = 10_000; // 100%
 pub const ADMIN_FEE_PCT: u64 = 50;
 pub const MIN_SWAP: u64 = 100_000;
 pub const MIN_DEPOSIT: u64 = 100_000_000;
 pub const NEWTON_ITERATIONS: u8 = 255;
 pub const RAMP_MIN_DURATION: i64 = 86_400; // 1 day
-pub const COMMIT_DELAY: i64 = 3_600;       // 1 hour
-pub const MIGRATION_FEE_BPS: u64 = 1337;   // 0.1337%
+pub const COMMIT_DELAY: i64 = 3_600; // 1 hour
+pub const MIGRATION_FEE_BPS: u64 = 1337; // 0.1337%
+pub const MIGRATION_FEE_DENOM: u64 = 1_000_000;
 pub const MAX_TOKENS: usize = 8;
+pub const MAX_REGISTRY_POOLS: usize = 128;
 pub const POOL_SIZE: usize = 1024;
 pub const NPOOL_SIZE: usize = 2048;
+/// `Registry` account: 96-byte header plus `MAX_REGISTRY_POOLS` pool keys
+pub const REGISTRY_SIZE: usize = 96 + 32 * MAX_REGISTRY_POOLS;
 pub const OHLCV_24H: usize = 24;
 pub const OHLCV_7D: usize = 7;
 
@@ -112,3 +120,17 @@ pub enum TwapWindow {
     Hour24 = 2,
     Day7 = 3,
 }
+
+impl TryFrom<u8> for TwapWindow {
+    type Error = u8;
+
+    fn try_from(v: u8) -> Result<Self, Self::Error> {
+        match v {
+            0 => Ok(Self::Hour1),
+            1 => Ok(Self::Hour4),
+            2 => Ok(Self::Hour24),
+            3 => Ok(Self::Day7),
+            _ => Err(v),
+        }
+    }
+}
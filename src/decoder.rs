@@ -0,0 +1,416 @@
+//! Account-level instruction decoding
+//!
+//! Pairs the decoded instruction data with the role of every account,
+//! following the account order of the builders in `instruction`.
+
+use solana_program::{instruction::Instruction, program_error::ProgramError, pubkey::Pubkey};
+
+use crate::constants::{MAX_TOKENS, PROGRAM_ID};
+use crate::instruction::AeX402Instruction;
+
+/// An account together with its role in the instruction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NamedAccount {
+    pub role: String,
+    pub pubkey: Pubkey,
+}
+
+/// A fully decoded AeX402 instruction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodedInstruction {
+    pub instruction: AeX402Instruction,
+    pub accounts: Vec<NamedAccount>,
+    /// Accounts past the fixed layout (transfer hook extras on swaps,
+    /// liquidity and hook execution)
+    pub remaining_accounts: Vec<Pubkey>,
+}
+
+impl DecodedInstruction {
+    /// Look up an account by role, e.g. `"pool"`, `"vault0"`, `"user"`
+    pub fn account(&self, role: &str) -> Option<&Pubkey> {
+        self.accounts
+            .iter()
+            .find(|a| a.role == role)
+            .map(|a| &a.pubkey)
+    }
+}
+
+/// Decode an `Instruction` (see `decode_instruction`)
+pub fn decode(ix: &Instruction) -> Result<DecodedInstruction, ProgramError> {
+    let keys: Vec<Pubkey> = ix.accounts.iter().map(|a| a.pubkey).collect();
+    decode_instruction(&ix.program_id, &keys, &ix.data)
+}
+
+/// Decode a compiled instruction given its program id, resolved account
+/// keys (in instruction order) and data.
+///
+/// Fails with `NotEnoughAccountKeys` when accounts are missing and
+/// `InvalidArgument` when there are more than the layout allows.
+pub fn decode_instruction(
+    program_id: &Pubkey,
+    account_keys: &[Pubkey],
+    data: &[u8],
+) -> Result<DecodedInstruction, ProgramError> {
+    if *program_id != PROGRAM_ID {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    let instruction = AeX402Instruction::unpack(data)?;
+    let (roles, allows_remaining) = account_layout(&instruction, account_keys.len())?;
+
+    if account_keys.len() < roles.len() {
+        return Err(ProgramError::NotEnoughAccountKeys);
+    }
+    if account_keys.len() > roles.len() && !allows_remaining {
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    let (fixed, remaining) = account_keys.split_at(roles.len());
+    let accounts = roles
+        .into_iter()
+        .zip(fixed)
+        .map(|(role, pubkey)| NamedAccount {
+            role,
+            pubkey: *pubkey,
+        })
+        .collect();
+
+    Ok(DecodedInstruction {
+        instruction,
+        accounts,
+        remaining_accounts: remaining.to_vec(),
+    })
+}
+
+fn roles(names: &[&str]) -> Vec<String> {
+    names.iter().map(|s| s.to_string()).collect()
+}
+
+fn indexed(prefix: &str, n: usize) -> impl Iterator<Item = String> + '_ {
+    (0..n).map(move |i| format!("{}{}", prefix, i))
+}
+
+/// Account roles for `ix`, and whether trailing accounts are permitted.
+/// `num_accounts` is only consulted where the data does not fix the layout.
+fn account_layout(
+    ix: &AeX402Instruction,
+    num_accounts: usize,
+) -> Result<(Vec<String>, bool), ProgramError> {
+    use AeX402Instruction::*;
+
+    let layout = match ix {
+        CreatePool { .. } => (
+            roles(&["pool", "mint0", "mint1", "authority", "system_program"]),
+            false,
+        ),
+        CreateNPool { n_tokens, .. } => {
+            let mut r = vec!["pool".to_string()];
+            r.extend(indexed("mint", *n_tokens as usize));
+            r.extend(roles(&["authority", "system_program"]));
+            (r, false)
+        }
+        InitT0Vault | InitT1Vault => (
+            roles(&["pool", "vault", "authority", "system_program"]),
+            false,
+        ),
+        InitLpMint => (
+            roles(&["pool", "lp_mint", "authority", "system_program"]),
+            false,
+        ),
+
+        Swap { .. }
+        | SwapT0T1 { .. }
+        | SwapT1T0 { .. }
+        | MigrateT0T1 { .. }
+        | MigrateT1T0 { .. } => (
+            roles(&[
+                "pool",
+                "vault0",
+                "vault1",
+                "user_token0",
+                "user_token1",
+                "user",
+                "token_program",
+            ]),
+            true,
+        ),
+        SwapN { .. } => (
+            roles(&[
+                "pool",
+                "vault_in",
+                "vault_out",
+                "user_token_in",
+                "user_token_out",
+                "user",
+                "token_program",
+            ]),
+            true,
+        ),
+
+        AddLiquidity { .. } | RemoveLiquidity { .. } => (
+            roles(&[
+                "pool",
+                "vault0",
+                "vault1",
+                "lp_mint",
+                "user_token0",
+                "user_token1",
+                "user_lp",
+                "user",
+                "token_program",
+            ]),
+            true,
+        ),
+        AddLiquiditySingle { .. } => (
+            roles(&[
+                "pool",
+                "vault0",
+                "vault1",
+                "lp_mint",
+                "user_token",
+                "user_lp",
+                "user",
+                "token_program",
+            ]),
+            true,
+        ),
+        AddLiquidityN {
+            amounts: per_token, ..
+        }
+        | RemoveLiquidityN {
+            mins: per_token, ..
+        } => {
+            let n = per_token.len();
+            let mut r = vec!["pool".to_string()];
+            r.extend(indexed("vault", n));
+            r.push("lp_mint".to_string());
+            r.extend(indexed("user_token", n));
+            r.extend(roles(&["user_lp", "user", "token_program"]));
+            (r, true)
+        }
+
+        SetPause { .. }
+        | UpdateFee { .. }
+        | CommitAmp { .. }
+        | RampAmp { .. }
+        | StopRamp
+        | CancelAuthTransfer => (roles(&["pool", "authority"]), false),
+        WithdrawAdminFees => {
+            // 2-token and N-token pools share the discriminator; the
+            // account count (2n + 3) determines n
+            let n = num_accounts.saturating_sub(3) / 2;
+            if !(2..=MAX_TOKENS).contains(&n) {
+                return Err(ProgramError::NotEnoughAccountKeys);
+            }
+            let mut r = vec!["pool".to_string()];
+            r.extend(indexed("vault", n));
+            r.extend(indexed("dest", n));
+            r.extend(roles(&["authority", "token_program"]));
+            (r, false)
+        }
+        InitAuthTransfer => (roles(&["pool", "authority", "new_authority"]), false),
+        CompleteAuthTransfer => (roles(&["pool", "new_authority"]), false),
+
+        CreateFarm { .. } => (
+            roles(&[
+                "farm",
+                "pool",
+                "reward_mint",
+                "reward_vault",
+                "lp_vault",
+                "authority",
+                "system_program",
+            ]),
+            false,
+        ),
+        StakeLp { .. } | UnstakeLp { .. } | LockLp { .. } | ClaimUnlockedLp => (
+            roles(&[
+                "user_position",
+                "farm",
+                "user_lp",
+                "lp_vault",
+                "user",
+                "token_program",
+            ]),
+            false,
+        ),
+        ClaimFarm => (
+            roles(&[
+                "user_position",
+                "farm",
+                "pool",
+                "reward_vault",
+                "user_reward",
+                "user",
+                "token_program",
+            ]),
+            false,
+        ),
+
+        EnterLottery { .. } => (
+            roles(&[
+                "lottery",
+                "entry",
+                "user_lp",
+                "lp_vault",
+                "user",
+                "token_program",
+                "system_program",
+            ]),
+            false,
+        ),
+        DrawLottery => (roles(&["lottery", "authority", "slot_hashes"]), false),
+        ClaimLottery => (
+            roles(&[
+                "lottery",
+                "entry",
+                "lp_vault",
+                "winner_lp",
+                "claimer",
+                "token_program",
+            ]),
+            false,
+        ),
+
+        InitRegistry => (roles(&["registry", "authority", "system_program"]), false),
+        RegisterPool | UnregisterPool => (roles(&["registry", "pool", "authority"]), false),
+        InitRegistryAuthTransfer => (roles(&["registry", "authority", "new_authority"]), false),
+        CompleteRegistryAuthTransfer => (roles(&["registry", "new_authority"]), false),
+        CancelRegistryAuthTransfer => (roles(&["registry", "authority"]), false),
+
+        GetTwap { .. } => (roles(&["pool"]), false),
+
+        InitTransferHook => (
+            roles(&["extra_account_metas", "mint", "authority", "system_program"]),
+            false,
+        ),
+        ExecuteTransferHook { .. } => (
+            roles(&[
+                "source",
+                "mint",
+                "destination",
+                "owner",
+                "extra_account_metas",
+            ]),
+            true,
+        ),
+    };
+
+    Ok(layout)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::TwapWindow;
+    use crate::instruction;
+
+    #[test]
+    fn test_decode_builders() {
+        let k: Vec<Pubkey> = (0..8).map(|_| Pubkey::new_unique()).collect();
+        let vaults = [k[0], k[1], k[2]];
+        let users = [k[3], k[4], k[5]];
+
+        let ix = instruction::swap_t0_t1(&k[0], &k[1], &k[2], &k[3], &k[4], &k[5], 10, 9, None);
+        let decoded = decode(&ix).unwrap();
+        assert_eq!(decoded.account("pool"), Some(&k[0]));
+        assert_eq!(decoded.account("vault1"), Some(&k[2]));
+        assert_eq!(decoded.account("user_token1"), Some(&k[4]));
+        assert_eq!(decoded.account("user"), Some(&k[5]));
+
+        let ix = instruction::add_liquidity_n(
+            &k[6],
+            &vaults,
+            &k[7],
+            &users,
+            &k[0],
+            &k[1],
+            &[1, 2, 3],
+            4,
+            None,
+        )
+        .unwrap();
+        let decoded = decode(&ix).unwrap();
+        assert_eq!(decoded.account("vault2"), Some(&k[2]));
+        assert_eq!(decoded.account("lp_mint"), Some(&k[7]));
+        assert_eq!(decoded.account("user_token0"), Some(&k[3]));
+        assert_eq!(decoded.account("user_lp"), Some(&k[0]));
+
+        let ix = instruction::withdraw_admin_fees_n(&k[6], &vaults, &users, &k[7], None).unwrap();
+        let decoded = decode(&ix).unwrap();
+        assert_eq!(decoded.account("dest2"), Some(&k[5]));
+        assert_eq!(decoded.account("authority"), Some(&k[7]));
+
+        let ix = instruction::withdraw_admin_fees(&k[0], &k[1], &k[2], &k[3], &k[4], &k[5], None);
+        assert_eq!(decode(&ix).unwrap().account("dest1"), Some(&k[4]));
+
+        // Every builder's layout matches the decoder exactly
+        let built = vec![
+            instruction::create_pool(&k[0], &k[1], &k[2], &k[3], 100, 255),
+            instruction::create_npool(&k[0], &vaults, &k[3], 100, 255).unwrap(),
+            instruction::init_t0_vault(&k[0], &k[1], &k[2]),
+            instruction::init_lp_mint(&k[0], &k[1], &k[2]),
+            instruction::swap(
+                &k[0], &k[1], &k[2], &k[3], &k[4], &k[5], 0, 1, 1, 1, 0, None,
+            ),
+            instruction::swap_n(&k[0], &vaults, &k[3], &k[4], &k[5], 0, 2, 1, 1, 0, None).unwrap(),
+            instruction::migrate_t1_t0(&k[0], &k[1], &k[2], &k[3], &k[4], &k[5], 1, 1, None),
+            instruction::add_liquidity(
+                &k[0], &k[1], &k[2], &k[3], &k[4], &k[5], &k[6], &k[7], 1, 1, 1, None,
+            ),
+            instruction::add_liquidity_single(
+                &k[0], &k[1], &k[2], &k[3], &k[4], &k[5], &k[6], 0, 1, 1, None,
+            ),
+            instruction::remove_liquidity_n(
+                &k[0], &vaults, &k[1], &users, &k[2], &k[3], 1, &[1; 3], None,
+            )
+            .unwrap(),
+            instruction::set_pause(&k[0], &k[1], true),
+            instruction::ramp_amp(&k[0], &k[1], 200, 86_400),
+            instruction::init_auth_transfer(&k[0], &k[1], &k[2]),
+            instruction::create_farm(&k[0], &k[1], &k[2], &k[3], &k[4], &k[5], 1, 0, 1),
+            instruction::lock_lp(&k[0], &k[1], &k[2], &k[3], &k[4], 1, 1, None),
+            instruction::claim_farm(&k[0], &k[1], &k[2], &k[3], &k[4], &k[5], None),
+            instruction::enter_lottery(&k[0], &k[1], &k[2], &k[3], &k[4], 1, None),
+            instruction::draw_lottery(&k[0], &k[1]),
+            instruction::claim_lottery(&k[0], &k[1], &k[2], &k[3], &k[4], None),
+            instruction::init_registry(&k[0], &k[1]),
+            instruction::register_pool(&k[0], &k[1], &k[2]),
+            instruction::complete_registry_auth_transfer(&k[0], &k[1]),
+            instruction::get_twap(&k[0], TwapWindow::Hour1),
+            instruction::init_transfer_hook(&k[0], &k[1], &k[2]),
+            instruction::execute_transfer_hook(&k[0], &k[1], &k[2], &k[3], &k[4], 1),
+        ];
+        for ix in built {
+            let decoded = decode(&ix).unwrap();
+            assert_eq!(decoded.accounts.len(), ix.accounts.len());
+            assert!(decoded.remaining_accounts.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_account_count_mismatch() {
+        let k: Vec<Pubkey> = (0..8).map(|_| Pubkey::new_unique()).collect();
+
+        let ix = instruction::swap_t0_t1(&k[0], &k[1], &k[2], &k[3], &k[4], &k[5], 10, 9, None);
+        assert_eq!(
+            decode_instruction(&ix.program_id, &k[..6], &ix.data),
+            Err(ProgramError::NotEnoughAccountKeys)
+        );
+
+        // Transfer hook extras are allowed on swaps
+        let decoded = decode_instruction(&ix.program_id, &k, &ix.data).unwrap();
+        assert_eq!(decoded.remaining_accounts, vec![k[7]]);
+
+        let ix = instruction::set_pause(&k[0], &k[1], true);
+        assert_eq!(
+            decode_instruction(&ix.program_id, &k[..3], &ix.data),
+            Err(ProgramError::InvalidArgument)
+        );
+
+        assert_eq!(
+            decode_instruction(&Pubkey::new_unique(), &k[..2], &ix.data),
+            Err(ProgramError::IncorrectProgramId)
+        );
+    }
+}
@@ -55,6 +55,68 @@ pub enum AeX402Error {
 
     #[error("CPI call failed")]
     CpiFailed = 6016,
+
+    // Raised by SDK-side validation only; the program never returns these
+    #[error("Invalid token index or count")]
+    InvalidToken = 7000,
+
+    #[error("Fee out of range")]
+    InvalidFee = 7001,
+}
+
+/// Why an off-chain math quote failed
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MathError {
+    #[error("Newton iteration did not converge")]
+    NonConvergence,
+
+    #[error("Math overflow")]
+    Overflow,
+
+    #[error("Pool balance is zero")]
+    ZeroBalance,
+
+    #[error("Amplification coefficient is zero")]
+    ZeroAmp,
+
+    #[error("Insufficient liquidity")]
+    InsufficientLiquidity,
+
+    #[error("Amount is zero")]
+    ZeroAmount,
+
+    #[error("Amount below minimum swap")]
+    BelowMinSwap,
+
+    #[error("Amount below minimum deposit")]
+    BelowMinDeposit,
+
+    #[error("Invalid token index or count")]
+    InvalidToken,
+
+    #[error("Amp ramp ends before it starts")]
+    InvalidRamp,
+
+    #[error("Token rate is zero")]
+    ZeroRate,
+}
+
+impl From<MathError> for AeX402Error {
+    fn from(err: MathError) -> Self {
+        match err {
+            MathError::NonConvergence => Self::InvalidInvariant,
+            MathError::Overflow => Self::MathOverflow,
+            MathError::ZeroBalance => Self::InsufficientLiquidity,
+            MathError::ZeroAmp => Self::InvalidAmp,
+            MathError::InsufficientLiquidity => Self::InsufficientLiquidity,
+            MathError::ZeroAmount => Self::ZeroAmount,
+            MathError::BelowMinSwap => Self::ZeroAmount,
+            MathError::BelowMinDeposit => Self::ZeroAmount,
+            MathError::InvalidToken => Self::InvalidToken,
+            MathError::InvalidRamp => Self::RampConstraint,
+            MathError::ZeroRate => Self::InvalidToken,
+        }
+    }
 }
 
 impl From<u32> for AeX402Error {
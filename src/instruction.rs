@@ -1,12 +1,15 @@
-//! Instruction builders
+//! Instruction data encoding/decoding and builders
+
+#![allow(clippy::too_many_arguments)]
 
 use solana_program::{
     instruction::{AccountMeta, Instruction},
+    program_error::ProgramError,
     pubkey::Pubkey,
-    system_program,
+    system_program, sysvar,
 };
 
-use crate::constants::{disc, PROGRAM_ID, TOKEN_PROGRAM_ID, TwapWindow};
+use crate::constants::{disc, TwapWindow, MAX_TOKENS, PROGRAM_ID, TOKEN_PROGRAM_ID};
 
 // ============================================================================
 // Helper Functions
@@ -24,6 +27,620 @@ fn write_i64(buf: &mut Vec<u8>, v: i64) {
     buf.extend_from_slice(&v.to_le_bytes());
 }
 
+/// Check that an N-token pool has between 2 and `MAX_TOKENS` tokens
+fn check_n_tokens(n: usize) -> Result<(), ProgramError> {
+    if !(2..=MAX_TOKENS).contains(&n) {
+        return Err(ProgramError::InvalidArgument);
+    }
+    Ok(())
+}
+
+/// Check that every per-token slice has exactly `n` entries
+fn check_lengths(n: usize, lens: &[usize]) -> Result<(), ProgramError> {
+    check_n_tokens(n)?;
+    if lens.iter().any(|&len| len != n) {
+        return Err(ProgramError::NotEnoughAccountKeys);
+    }
+    Ok(())
+}
+
+fn read_u8(input: &[u8]) -> Result<(u8, &[u8]), ProgramError> {
+    input
+        .split_first()
+        .map(|(&v, rest)| (v, rest))
+        .ok_or(ProgramError::InvalidInstructionData)
+}
+
+fn read_u64(input: &[u8]) -> Result<(u64, &[u8]), ProgramError> {
+    if input.len() < 8 {
+        return Err(ProgramError::InvalidInstructionData);
+    }
+    let (bytes, rest) = input.split_at(8);
+    Ok((u64::from_le_bytes(bytes.try_into().unwrap()), rest))
+}
+
+fn read_i64(input: &[u8]) -> Result<(i64, &[u8]), ProgramError> {
+    let (v, rest) = read_u64(input)?;
+    Ok((v as i64, rest))
+}
+
+/// Read `input` as a run of u64s, failing on a partial trailing word
+fn read_u64s(input: &[u8]) -> Result<Vec<u64>, ProgramError> {
+    if input.len() % 8 != 0 {
+        return Err(ProgramError::InvalidInstructionData);
+    }
+    Ok(input
+        .chunks_exact(8)
+        .map(|c| u64::from_le_bytes(c.try_into().unwrap()))
+        .collect())
+}
+
+// ============================================================================
+// Instruction Data
+// ============================================================================
+
+/// Decoded AeX402 instruction data, one variant per discriminator in
+/// `constants::disc`.
+///
+/// The builders below encode through `pack`, so `unpack(&ix.data)` always
+/// round-trips a built instruction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AeX402Instruction {
+    // Pool creation
+    CreatePool {
+        amp: u64,
+        bump: u8,
+    },
+    CreateNPool {
+        amp: u64,
+        n_tokens: u8,
+        bump: u8,
+    },
+    InitT0Vault,
+    InitT1Vault,
+    InitLpMint,
+
+    // Swaps
+    Swap {
+        from: u8,
+        to: u8,
+        amount_in: u64,
+        min_out: u64,
+        deadline: i64,
+    },
+    SwapT0T1 {
+        amount_in: u64,
+        min_out: u64,
+    },
+    SwapT1T0 {
+        amount_in: u64,
+        min_out: u64,
+    },
+    SwapN {
+        from: u8,
+        to: u8,
+        amount_in: u64,
+        min_out: u64,
+        deadline: i64,
+    },
+    MigrateT0T1 {
+        amount_in: u64,
+        min_out: u64,
+    },
+    MigrateT1T0 {
+        amount_in: u64,
+        min_out: u64,
+    },
+
+    // Liquidity
+    AddLiquidity {
+        amount0: u64,
+        amount1: u64,
+        min_lp: u64,
+    },
+    AddLiquiditySingle {
+        index: u8,
+        amount: u64,
+        min_lp: u64,
+    },
+    AddLiquidityN {
+        amounts: Vec<u64>,
+        min_lp: u64,
+    },
+    RemoveLiquidity {
+        lp_amount: u64,
+        min0: u64,
+        min1: u64,
+    },
+    RemoveLiquidityN {
+        lp_amount: u64,
+        mins: Vec<u64>,
+    },
+
+    // Admin
+    SetPause {
+        paused: bool,
+    },
+    UpdateFee {
+        fee_bps: u64,
+    },
+    WithdrawAdminFees,
+    CommitAmp {
+        target_amp: u64,
+    },
+    RampAmp {
+        target_amp: u64,
+        duration: i64,
+    },
+    StopRamp,
+    InitAuthTransfer,
+    CompleteAuthTransfer,
+    CancelAuthTransfer,
+
+    // Farming
+    CreateFarm {
+        reward_rate: u64,
+        start_time: i64,
+        end_time: i64,
+    },
+    StakeLp {
+        amount: u64,
+    },
+    UnstakeLp {
+        amount: u64,
+    },
+    ClaimFarm,
+    LockLp {
+        amount: u64,
+        duration: i64,
+    },
+    ClaimUnlockedLp,
+
+    // Lottery
+    EnterLottery {
+        ticket_count: u64,
+    },
+    DrawLottery,
+    ClaimLottery,
+
+    // Registry
+    InitRegistry,
+    RegisterPool,
+    UnregisterPool,
+    InitRegistryAuthTransfer,
+    CompleteRegistryAuthTransfer,
+    CancelRegistryAuthTransfer,
+
+    // Oracle
+    GetTwap {
+        window: TwapWindow,
+    },
+
+    // Transfer Hook
+    InitTransferHook,
+    ExecuteTransferHook {
+        amount: u64,
+    },
+}
+
+impl AeX402Instruction {
+    /// Decode instruction data. Trailing bytes are rejected.
+    pub fn unpack(input: &[u8]) -> Result<Self, ProgramError> {
+        use AeX402Instruction::*;
+
+        let (tag, rest) = read_u64(input)?;
+
+        let (ix, rest) = match tag {
+            disc::CREATEPOOL => {
+                let (amp, rest) = read_u64(rest)?;
+                let (bump, rest) = read_u8(rest)?;
+                (CreatePool { amp, bump }, rest)
+            }
+            disc::CREATEPN => {
+                let (amp, rest) = read_u64(rest)?;
+                let (n_tokens, rest) = read_u8(rest)?;
+                let (bump, rest) = read_u8(rest)?;
+                check_n_tokens(n_tokens as usize)
+                    .map_err(|_| ProgramError::InvalidInstructionData)?;
+                (
+                    CreateNPool {
+                        amp,
+                        n_tokens,
+                        bump,
+                    },
+                    rest,
+                )
+            }
+            disc::INITT0V => (InitT0Vault, rest),
+            disc::INITT1V => (InitT1Vault, rest),
+            disc::INITLPM => (InitLpMint, rest),
+
+            disc::SWAP | disc::SWAPN => {
+                let (from, rest) = read_u8(rest)?;
+                let (to, rest) = read_u8(rest)?;
+                let (amount_in, rest) = read_u64(rest)?;
+                let (min_out, rest) = read_u64(rest)?;
+                let (deadline, rest) = read_i64(rest)?;
+                if tag == disc::SWAP {
+                    (
+                        Swap {
+                            from,
+                            to,
+                            amount_in,
+                            min_out,
+                            deadline,
+                        },
+                        rest,
+                    )
+                } else {
+                    (
+                        SwapN {
+                            from,
+                            to,
+                            amount_in,
+                            min_out,
+                            deadline,
+                        },
+                        rest,
+                    )
+                }
+            }
+            disc::SWAPT0T1 | disc::SWAPT1T0 | disc::MIGT0T1 | disc::MIGT1T0 => {
+                let (amount_in, rest) = read_u64(rest)?;
+                let (min_out, rest) = read_u64(rest)?;
+                let ix = match tag {
+                    disc::SWAPT0T1 => SwapT0T1 { amount_in, min_out },
+                    disc::SWAPT1T0 => SwapT1T0 { amount_in, min_out },
+                    disc::MIGT0T1 => MigrateT0T1 { amount_in, min_out },
+                    _ => MigrateT1T0 { amount_in, min_out },
+                };
+                (ix, rest)
+            }
+
+            disc::ADDLIQ => {
+                let (amount0, rest) = read_u64(rest)?;
+                let (amount1, rest) = read_u64(rest)?;
+                let (min_lp, rest) = read_u64(rest)?;
+                (
+                    AddLiquidity {
+                        amount0,
+                        amount1,
+                        min_lp,
+                    },
+                    rest,
+                )
+            }
+            disc::ADDLIQ1 => {
+                let (index, rest) = read_u8(rest)?;
+                let (amount, rest) = read_u64(rest)?;
+                let (min_lp, rest) = read_u64(rest)?;
+                (
+                    AddLiquiditySingle {
+                        index,
+                        amount,
+                        min_lp,
+                    },
+                    rest,
+                )
+            }
+            disc::ADDLIQN => {
+                // amounts[n] followed by min_lp
+                let mut amounts = read_u64s(rest)?;
+                let min_lp = amounts.pop().ok_or(ProgramError::InvalidInstructionData)?;
+                check_n_tokens(amounts.len()).map_err(|_| ProgramError::InvalidInstructionData)?;
+                (AddLiquidityN { amounts, min_lp }, &[][..])
+            }
+            disc::REMLIQ => {
+                let (lp_amount, rest) = read_u64(rest)?;
+                let (min0, rest) = read_u64(rest)?;
+                let (min1, rest) = read_u64(rest)?;
+                (
+                    RemoveLiquidity {
+                        lp_amount,
+                        min0,
+                        min1,
+                    },
+                    rest,
+                )
+            }
+            disc::REMLIQN => {
+                // lp_amount followed by mins[n]
+                let (lp_amount, rest) = read_u64(rest)?;
+                let mins = read_u64s(rest)?;
+                check_n_tokens(mins.len()).map_err(|_| ProgramError::InvalidInstructionData)?;
+                (RemoveLiquidityN { lp_amount, mins }, &[][..])
+            }
+
+            disc::SETPAUSE => {
+                let (paused, rest) = read_u8(rest)?;
+                let paused = match paused {
+                    0 => false,
+                    1 => true,
+                    _ => return Err(ProgramError::InvalidInstructionData),
+                };
+                (SetPause { paused }, rest)
+            }
+            disc::UPDFEE => {
+                let (fee_bps, rest) = read_u64(rest)?;
+                (UpdateFee { fee_bps }, rest)
+            }
+            disc::WDRAWFEE => (WithdrawAdminFees, rest),
+            disc::COMMITAMP => {
+                let (target_amp, rest) = read_u64(rest)?;
+                (CommitAmp { target_amp }, rest)
+            }
+            disc::RAMPAMP => {
+                let (target_amp, rest) = read_u64(rest)?;
+                let (duration, rest) = read_i64(rest)?;
+                (
+                    RampAmp {
+                        target_amp,
+                        duration,
+                    },
+                    rest,
+                )
+            }
+            disc::STOPRAMP => (StopRamp, rest),
+            disc::INITAUTH => (InitAuthTransfer, rest),
+            disc::COMPLAUTH => (CompleteAuthTransfer, rest),
+            disc::CANCELAUTH => (CancelAuthTransfer, rest),
+
+            disc::CREATEFARM => {
+                let (reward_rate, rest) = read_u64(rest)?;
+                let (start_time, rest) = read_i64(rest)?;
+                let (end_time, rest) = read_i64(rest)?;
+                (
+                    CreateFarm {
+                        reward_rate,
+                        start_time,
+                        end_time,
+                    },
+                    rest,
+                )
+            }
+            disc::STAKELP => {
+                let (amount, rest) = read_u64(rest)?;
+                (StakeLp { amount }, rest)
+            }
+            disc::UNSTAKELP => {
+                let (amount, rest) = read_u64(rest)?;
+                (UnstakeLp { amount }, rest)
+            }
+            disc::CLAIMFARM => (ClaimFarm, rest),
+            disc::LOCKLP => {
+                let (amount, rest) = read_u64(rest)?;
+                let (duration, rest) = read_i64(rest)?;
+                (LockLp { amount, duration }, rest)
+            }
+            disc::CLAIMULP => (ClaimUnlockedLp, rest),
+
+            disc::ENTERLOT => {
+                let (ticket_count, rest) = read_u64(rest)?;
+                (EnterLottery { ticket_count }, rest)
+            }
+            disc::DRAWLOT => (DrawLottery, rest),
+            disc::CLAIMLOT => (ClaimLottery, rest),
+
+            disc::INITREG => (InitRegistry, rest),
+            disc::REGPOOL => (RegisterPool, rest),
+            disc::UNREGPOOL => (UnregisterPool, rest),
+            disc::INITREGA => (InitRegistryAuthTransfer, rest),
+            disc::COMPLREGA => (CompleteRegistryAuthTransfer, rest),
+            disc::CANCELREGA => (CancelRegistryAuthTransfer, rest),
+
+            disc::GETTWAP => {
+                let (window, rest) = read_u8(rest)?;
+                let window = TwapWindow::try_from(window)
+                    .map_err(|_| ProgramError::InvalidInstructionData)?;
+                (GetTwap { window }, rest)
+            }
+
+            disc::TH_INIT => (InitTransferHook, rest),
+            disc::TH_EXEC => {
+                let (amount, rest) = read_u64(rest)?;
+                (ExecuteTransferHook { amount }, rest)
+            }
+
+            _ => return Err(ProgramError::InvalidInstructionData),
+        };
+
+        if !rest.is_empty() {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+        Ok(ix)
+    }
+
+    /// Encode instruction data
+    pub fn pack(&self) -> Vec<u8> {
+        use AeX402Instruction::*;
+
+        let mut buf = Vec::with_capacity(34);
+        write_u64(&mut buf, self.discriminator());
+
+        match self {
+            CreatePool { amp, bump } => {
+                write_u64(&mut buf, *amp);
+                write_u8(&mut buf, *bump);
+            }
+            CreateNPool {
+                amp,
+                n_tokens,
+                bump,
+            } => {
+                write_u64(&mut buf, *amp);
+                write_u8(&mut buf, *n_tokens);
+                write_u8(&mut buf, *bump);
+            }
+            Swap {
+                from,
+                to,
+                amount_in,
+                min_out,
+                deadline,
+            }
+            | SwapN {
+                from,
+                to,
+                amount_in,
+                min_out,
+                deadline,
+            } => {
+                write_u8(&mut buf, *from);
+                write_u8(&mut buf, *to);
+                write_u64(&mut buf, *amount_in);
+                write_u64(&mut buf, *min_out);
+                write_i64(&mut buf, *deadline);
+            }
+            SwapT0T1 { amount_in, min_out }
+            | SwapT1T0 { amount_in, min_out }
+            | MigrateT0T1 { amount_in, min_out }
+            | MigrateT1T0 { amount_in, min_out } => {
+                write_u64(&mut buf, *amount_in);
+                write_u64(&mut buf, *min_out);
+            }
+            AddLiquidity {
+                amount0,
+                amount1,
+                min_lp,
+            } => {
+                write_u64(&mut buf, *amount0);
+                write_u64(&mut buf, *amount1);
+                write_u64(&mut buf, *min_lp);
+            }
+            AddLiquiditySingle {
+                index,
+                amount,
+                min_lp,
+            } => {
+                write_u8(&mut buf, *index);
+                write_u64(&mut buf, *amount);
+                write_u64(&mut buf, *min_lp);
+            }
+            AddLiquidityN { amounts, min_lp } => {
+                for &amount in amounts {
+                    write_u64(&mut buf, amount);
+                }
+                write_u64(&mut buf, *min_lp);
+            }
+            RemoveLiquidity {
+                lp_amount,
+                min0,
+                min1,
+            } => {
+                write_u64(&mut buf, *lp_amount);
+                write_u64(&mut buf, *min0);
+                write_u64(&mut buf, *min1);
+            }
+            RemoveLiquidityN { lp_amount, mins } => {
+                write_u64(&mut buf, *lp_amount);
+                for &min in mins {
+                    write_u64(&mut buf, min);
+                }
+            }
+            SetPause { paused } => write_u8(&mut buf, if *paused { 1 } else { 0 }),
+            UpdateFee { fee_bps } => write_u64(&mut buf, *fee_bps),
+            CommitAmp { target_amp } => write_u64(&mut buf, *target_amp),
+            RampAmp {
+                target_amp,
+                duration,
+            } => {
+                write_u64(&mut buf, *target_amp);
+                write_i64(&mut buf, *duration);
+            }
+            CreateFarm {
+                reward_rate,
+                start_time,
+                end_time,
+            } => {
+                write_u64(&mut buf, *reward_rate);
+                write_i64(&mut buf, *start_time);
+                write_i64(&mut buf, *end_time);
+            }
+            StakeLp { amount } | UnstakeLp { amount } | ExecuteTransferHook { amount } => {
+                write_u64(&mut buf, *amount);
+            }
+            LockLp { amount, duration } => {
+                write_u64(&mut buf, *amount);
+                write_i64(&mut buf, *duration);
+            }
+            EnterLottery { ticket_count } => write_u64(&mut buf, *ticket_count),
+            GetTwap { window } => write_u8(&mut buf, *window as u8),
+            InitT0Vault
+            | InitT1Vault
+            | InitLpMint
+            | WithdrawAdminFees
+            | StopRamp
+            | InitAuthTransfer
+            | CompleteAuthTransfer
+            | CancelAuthTransfer
+            | ClaimFarm
+            | ClaimUnlockedLp
+            | DrawLottery
+            | ClaimLottery
+            | InitRegistry
+            | RegisterPool
+            | UnregisterPool
+            | InitRegistryAuthTransfer
+            | CompleteRegistryAuthTransfer
+            | CancelRegistryAuthTransfer
+            | InitTransferHook => {}
+        }
+
+        buf
+    }
+
+    /// The `constants::disc` value this instruction is tagged with
+    pub fn discriminator(&self) -> u64 {
+        use AeX402Instruction::*;
+
+        match self {
+            CreatePool { .. } => disc::CREATEPOOL,
+            CreateNPool { .. } => disc::CREATEPN,
+            InitT0Vault => disc::INITT0V,
+            InitT1Vault => disc::INITT1V,
+            InitLpMint => disc::INITLPM,
+            Swap { .. } => disc::SWAP,
+            SwapT0T1 { .. } => disc::SWAPT0T1,
+            SwapT1T0 { .. } => disc::SWAPT1T0,
+            SwapN { .. } => disc::SWAPN,
+            MigrateT0T1 { .. } => disc::MIGT0T1,
+            MigrateT1T0 { .. } => disc::MIGT1T0,
+            AddLiquidity { .. } => disc::ADDLIQ,
+            AddLiquiditySingle { .. } => disc::ADDLIQ1,
+            AddLiquidityN { .. } => disc::ADDLIQN,
+            RemoveLiquidity { .. } => disc::REMLIQ,
+            RemoveLiquidityN { .. } => disc::REMLIQN,
+            SetPause { .. } => disc::SETPAUSE,
+            UpdateFee { .. } => disc::UPDFEE,
+            WithdrawAdminFees => disc::WDRAWFEE,
+            CommitAmp { .. } => disc::COMMITAMP,
+            RampAmp { .. } => disc::RAMPAMP,
+            StopRamp => disc::STOPRAMP,
+            InitAuthTransfer => disc::INITAUTH,
+            CompleteAuthTransfer => disc::COMPLAUTH,
+            CancelAuthTransfer => disc::CANCELAUTH,
+            CreateFarm { .. } => disc::CREATEFARM,
+            StakeLp { .. } => disc::STAKELP,
+            UnstakeLp { .. } => disc::UNSTAKELP,
+            ClaimFarm => disc::CLAIMFARM,
+            LockLp { .. } => disc::LOCKLP,
+            ClaimUnlockedLp => disc::CLAIMULP,
+            EnterLottery { .. } => disc::ENTERLOT,
+            DrawLottery => disc::DRAWLOT,
+            ClaimLottery => disc::CLAIMLOT,
+            InitRegistry => disc::INITREG,
+            RegisterPool => disc::REGPOOL,
+            UnregisterPool => disc::UNREGPOOL,
+            InitRegistryAuthTransfer => disc::INITREGA,
+            CompleteRegistryAuthTransfer => disc::COMPLREGA,
+            CancelRegistryAuthTransfer => disc::CANCELREGA,
+            GetTwap { .. } => disc::GETTWAP,
+            InitTransferHook => disc::TH_INIT,
+            ExecuteTransferHook { .. } => disc::TH_EXEC,
+        }
+    }
+}
+
 // ============================================================================
 // Pool Creation
 // ============================================================================
@@ -36,10 +653,7 @@ pub fn create_pool(
     amp: u64,
     bump: u8,
 ) -> Instruction {
-    let mut data = Vec::with_capacity(17);
-    write_u64(&mut data, disc::CREATEPOOL);
-    write_u64(&mut data, amp);
-    write_u8(&mut data, bump);
+    let data = AeX402Instruction::CreatePool { amp, bump }.pack();
 
     Instruction {
         program_id: PROGRAM_ID,
@@ -54,11 +668,7 @@ pub fn create_pool(
     }
 }
 
-pub fn init_t0_vault(
-    pool: &Pubkey,
-    vault: &Pubkey,
-    authority: &Pubkey,
-) -> Instruction {
+pub fn init_t0_vault(pool: &Pubkey, vault: &Pubkey, authority: &Pubkey) -> Instruction {
     Instruction {
         program_id: PROGRAM_ID,
         accounts: vec![
@@ -67,15 +677,11 @@ pub fn init_t0_vault(
             AccountMeta::new_readonly(*authority, true),
             AccountMeta::new_readonly(system_program::ID, false),
         ],
-        data: disc::INITT0V.to_le_bytes().to_vec(),
+        data: AeX402Instruction::InitT0Vault.pack(),
     }
 }
 
-pub fn init_t1_vault(
-    pool: &Pubkey,
-    vault: &Pubkey,
-    authority: &Pubkey,
-) -> Instruction {
+pub fn init_t1_vault(pool: &Pubkey, vault: &Pubkey, authority: &Pubkey) -> Instruction {
     Instruction {
         program_id: PROGRAM_ID,
         accounts: vec![
@@ -84,15 +690,11 @@ pub fn init_t1_vault(
             AccountMeta::new_readonly(*authority, true),
             AccountMeta::new_readonly(system_program::ID, false),
         ],
-        data: disc::INITT1V.to_le_bytes().to_vec(),
+        data: AeX402Instruction::InitT1Vault.pack(),
     }
 }
 
-pub fn init_lp_mint(
-    pool: &Pubkey,
-    lp_mint: &Pubkey,
-    authority: &Pubkey,
-) -> Instruction {
+pub fn init_lp_mint(pool: &Pubkey, lp_mint: &Pubkey, authority: &Pubkey) -> Instruction {
     Instruction {
         program_id: PROGRAM_ID,
         accounts: vec![
@@ -101,7 +703,7 @@ pub fn init_lp_mint(
             AccountMeta::new_readonly(*authority, true),
             AccountMeta::new_readonly(system_program::ID, false),
         ],
-        data: disc::INITLPM.to_le_bytes().to_vec(),
+        data: AeX402Instruction::InitLpMint.pack(),
     }
 }
 
@@ -123,13 +725,14 @@ pub fn swap(
     deadline: i64,
     token_program: Option<&Pubkey>,
 ) -> Instruction {
-    let mut data = Vec::with_capacity(34);
-    write_u64(&mut data, disc::SWAP);
-    write_u8(&mut data, from);
-    write_u8(&mut data, to);
-    write_u64(&mut data, amount_in);
-    write_u64(&mut data, min_out);
-    write_i64(&mut data, deadline);
+    let data = AeX402Instruction::Swap {
+        from,
+        to,
+        amount_in,
+        min_out,
+        deadline,
+    }
+    .pack();
 
     Instruction {
         program_id: PROGRAM_ID,
@@ -157,10 +760,7 @@ pub fn swap_t0_t1(
     min_out: u64,
     token_program: Option<&Pubkey>,
 ) -> Instruction {
-    let mut data = Vec::with_capacity(24);
-    write_u64(&mut data, disc::SWAPT0T1);
-    write_u64(&mut data, amount_in);
-    write_u64(&mut data, min_out);
+    let data = AeX402Instruction::SwapT0T1 { amount_in, min_out }.pack();
 
     Instruction {
         program_id: PROGRAM_ID,
@@ -188,10 +788,65 @@ pub fn swap_t1_t0(
     min_out: u64,
     token_program: Option<&Pubkey>,
 ) -> Instruction {
-    let mut data = Vec::with_capacity(24);
-    write_u64(&mut data, disc::SWAPT1T0);
-    write_u64(&mut data, amount_in);
-    write_u64(&mut data, min_out);
+    let data = AeX402Instruction::SwapT1T0 { amount_in, min_out }.pack();
+
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: vec![
+            AccountMeta::new(*pool, false),
+            AccountMeta::new(*vault0, false),
+            AccountMeta::new(*vault1, false),
+            AccountMeta::new(*user_token0, false),
+            AccountMeta::new(*user_token1, false),
+            AccountMeta::new_readonly(*user, true),
+            AccountMeta::new_readonly(*token_program.unwrap_or(&TOKEN_PROGRAM_ID), false),
+        ],
+        data,
+    }
+}
+
+/// Migration swap token0 -> token1, charged `MIGRATION_FEE_BPS`
+pub fn migrate_t0_t1(
+    pool: &Pubkey,
+    vault0: &Pubkey,
+    vault1: &Pubkey,
+    user_token0: &Pubkey,
+    user_token1: &Pubkey,
+    user: &Pubkey,
+    amount_in: u64,
+    min_out: u64,
+    token_program: Option<&Pubkey>,
+) -> Instruction {
+    let data = AeX402Instruction::MigrateT0T1 { amount_in, min_out }.pack();
+
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: vec![
+            AccountMeta::new(*pool, false),
+            AccountMeta::new(*vault0, false),
+            AccountMeta::new(*vault1, false),
+            AccountMeta::new(*user_token0, false),
+            AccountMeta::new(*user_token1, false),
+            AccountMeta::new_readonly(*user, true),
+            AccountMeta::new_readonly(*token_program.unwrap_or(&TOKEN_PROGRAM_ID), false),
+        ],
+        data,
+    }
+}
+
+/// Migration swap token1 -> token0, charged `MIGRATION_FEE_BPS`
+pub fn migrate_t1_t0(
+    pool: &Pubkey,
+    vault0: &Pubkey,
+    vault1: &Pubkey,
+    user_token0: &Pubkey,
+    user_token1: &Pubkey,
+    user: &Pubkey,
+    amount_in: u64,
+    min_out: u64,
+    token_program: Option<&Pubkey>,
+) -> Instruction {
+    let data = AeX402Instruction::MigrateT1T0 { amount_in, min_out }.pack();
 
     Instruction {
         program_id: PROGRAM_ID,
@@ -226,11 +881,12 @@ pub fn add_liquidity(
     min_lp: u64,
     token_program: Option<&Pubkey>,
 ) -> Instruction {
-    let mut data = Vec::with_capacity(32);
-    write_u64(&mut data, disc::ADDLIQ);
-    write_u64(&mut data, amount0);
-    write_u64(&mut data, amount1);
-    write_u64(&mut data, min_lp);
+    let data = AeX402Instruction::AddLiquidity {
+        amount0,
+        amount1,
+        min_lp,
+    }
+    .pack();
 
     Instruction {
         program_id: PROGRAM_ID,
@@ -249,6 +905,43 @@ pub fn add_liquidity(
     }
 }
 
+/// Single-sided deposit of token `index` (0 or 1)
+pub fn add_liquidity_single(
+    pool: &Pubkey,
+    vault0: &Pubkey,
+    vault1: &Pubkey,
+    lp_mint: &Pubkey,
+    user_token: &Pubkey,
+    user_lp: &Pubkey,
+    user: &Pubkey,
+    index: u8,
+    amount: u64,
+    min_lp: u64,
+    token_program: Option<&Pubkey>,
+) -> Instruction {
+    let data = AeX402Instruction::AddLiquiditySingle {
+        index,
+        amount,
+        min_lp,
+    }
+    .pack();
+
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: vec![
+            AccountMeta::new(*pool, false),
+            AccountMeta::new(*vault0, false),
+            AccountMeta::new(*vault1, false),
+            AccountMeta::new(*lp_mint, false),
+            AccountMeta::new(*user_token, false),
+            AccountMeta::new(*user_lp, false),
+            AccountMeta::new_readonly(*user, true),
+            AccountMeta::new_readonly(*token_program.unwrap_or(&TOKEN_PROGRAM_ID), false),
+        ],
+        data,
+    }
+}
+
 pub fn remove_liquidity(
     pool: &Pubkey,
     vault0: &Pubkey,
@@ -263,11 +956,12 @@ pub fn remove_liquidity(
     min1: u64,
     token_program: Option<&Pubkey>,
 ) -> Instruction {
-    let mut data = Vec::with_capacity(32);
-    write_u64(&mut data, disc::REMLIQ);
-    write_u64(&mut data, lp_amount);
-    write_u64(&mut data, min0);
-    write_u64(&mut data, min1);
+    let data = AeX402Instruction::RemoveLiquidity {
+        lp_amount,
+        min0,
+        min1,
+    }
+    .pack();
 
     Instruction {
         program_id: PROGRAM_ID,
@@ -286,14 +980,173 @@ pub fn remove_liquidity(
     }
 }
 
+// ============================================================================
+// N-Token Pools
+// ============================================================================
+
+/// Create an N-token pool. `mints` must hold 2..=`MAX_TOKENS` entries.
+pub fn create_npool(
+    pool: &Pubkey,
+    mints: &[Pubkey],
+    authority: &Pubkey,
+    amp: u64,
+    bump: u8,
+) -> Result<Instruction, ProgramError> {
+    check_n_tokens(mints.len())?;
+
+    let data = AeX402Instruction::CreateNPool {
+        amp,
+        n_tokens: mints.len() as u8,
+        bump,
+    }
+    .pack();
+
+    let mut accounts = Vec::with_capacity(mints.len() + 3);
+    accounts.push(AccountMeta::new(*pool, false));
+    accounts.extend(mints.iter().map(|m| AccountMeta::new_readonly(*m, false)));
+    accounts.push(AccountMeta::new(*authority, true));
+    accounts.push(AccountMeta::new_readonly(system_program::ID, false));
+
+    Ok(Instruction {
+        program_id: PROGRAM_ID,
+        accounts,
+        data,
+    })
+}
+
+/// Swap token `from` for token `to` in an N-token pool. `vaults` must
+/// hold every pool vault in pool order; `from` and `to` index into it.
+pub fn swap_n(
+    pool: &Pubkey,
+    vaults: &[Pubkey],
+    user_token_in: &Pubkey,
+    user_token_out: &Pubkey,
+    user: &Pubkey,
+    from: u8,
+    to: u8,
+    amount_in: u64,
+    min_out: u64,
+    deadline: i64,
+    token_program: Option<&Pubkey>,
+) -> Result<Instruction, ProgramError> {
+    check_n_tokens(vaults.len())?;
+    let (vault_in, vault_out) = match (vaults.get(from as usize), vaults.get(to as usize)) {
+        (Some(vault_in), Some(vault_out)) if from != to => (vault_in, vault_out),
+        _ => return Err(ProgramError::InvalidArgument),
+    };
+
+    let data = AeX402Instruction::SwapN {
+        from,
+        to,
+        amount_in,
+        min_out,
+        deadline,
+    }
+    .pack();
+
+    Ok(Instruction {
+        program_id: PROGRAM_ID,
+        accounts: vec![
+            AccountMeta::new(*pool, false),
+            AccountMeta::new(*vault_in, false),
+            AccountMeta::new(*vault_out, false),
+            AccountMeta::new(*user_token_in, false),
+            AccountMeta::new(*user_token_out, false),
+            AccountMeta::new_readonly(*user, true),
+            AccountMeta::new_readonly(*token_program.unwrap_or(&TOKEN_PROGRAM_ID), false),
+        ],
+        data,
+    })
+}
+
+/// Deposit into an N-token pool. `vaults`, `user_tokens` and `amounts`
+/// must all have one entry per pool token, in pool order.
+pub fn add_liquidity_n(
+    pool: &Pubkey,
+    vaults: &[Pubkey],
+    lp_mint: &Pubkey,
+    user_tokens: &[Pubkey],
+    user_lp: &Pubkey,
+    user: &Pubkey,
+    amounts: &[u64],
+    min_lp: u64,
+    token_program: Option<&Pubkey>,
+) -> Result<Instruction, ProgramError> {
+    let n = vaults.len();
+    check_lengths(n, &[user_tokens.len(), amounts.len()])?;
+
+    let data = AeX402Instruction::AddLiquidityN {
+        amounts: amounts.to_vec(),
+        min_lp,
+    }
+    .pack();
+
+    let mut accounts = Vec::with_capacity(2 * n + 5);
+    accounts.push(AccountMeta::new(*pool, false));
+    accounts.extend(vaults.iter().map(|v| AccountMeta::new(*v, false)));
+    accounts.push(AccountMeta::new(*lp_mint, false));
+    accounts.extend(user_tokens.iter().map(|u| AccountMeta::new(*u, false)));
+    accounts.push(AccountMeta::new(*user_lp, false));
+    accounts.push(AccountMeta::new_readonly(*user, true));
+    accounts.push(AccountMeta::new_readonly(
+        *token_program.unwrap_or(&TOKEN_PROGRAM_ID),
+        false,
+    ));
+
+    Ok(Instruction {
+        program_id: PROGRAM_ID,
+        accounts,
+        data,
+    })
+}
+
+/// Withdraw from an N-token pool. `vaults`, `user_tokens` and `mins`
+/// must all have one entry per pool token, in pool order.
+pub fn remove_liquidity_n(
+    pool: &Pubkey,
+    vaults: &[Pubkey],
+    lp_mint: &Pubkey,
+    user_tokens: &[Pubkey],
+    user_lp: &Pubkey,
+    user: &Pubkey,
+    lp_amount: u64,
+    mins: &[u64],
+    token_program: Option<&Pubkey>,
+) -> Result<Instruction, ProgramError> {
+    let n = vaults.len();
+    check_lengths(n, &[user_tokens.len(), mins.len()])?;
+
+    let data = AeX402Instruction::RemoveLiquidityN {
+        lp_amount,
+        mins: mins.to_vec(),
+    }
+    .pack();
+
+    let mut accounts = Vec::with_capacity(2 * n + 5);
+    accounts.push(AccountMeta::new(*pool, false));
+    accounts.extend(vaults.iter().map(|v| AccountMeta::new(*v, false)));
+    accounts.push(AccountMeta::new(*lp_mint, false));
+    accounts.extend(user_tokens.iter().map(|u| AccountMeta::new(*u, false)));
+    accounts.push(AccountMeta::new(*user_lp, false));
+    accounts.push(AccountMeta::new_readonly(*user, true));
+    accounts.push(AccountMeta::new_readonly(
+        *token_program.unwrap_or(&TOKEN_PROGRAM_ID),
+        false,
+    ));
+
+    Ok(Instruction {
+        program_id: PROGRAM_ID,
+        accounts,
+        data,
+    })
+}
+
 // ============================================================================
 // Admin
 // ============================================================================
 
 pub fn set_pause(pool: &Pubkey, authority: &Pubkey, paused: bool) -> Instruction {
-    let mut data = Vec::with_capacity(9);
-    write_u64(&mut data, disc::SETPAUSE);
-    write_u8(&mut data, if paused { 1 } else { 0 });
+    let data = AeX402Instruction::SetPause { paused }.pack();
 
     Instruction {
         program_id: PROGRAM_ID,
@@ -306,9 +1159,7 @@ pub fn set_pause(pool: &Pubkey, authority: &Pubkey, paused: bool) -> Instruction
 }
 
 pub fn update_fee(pool: &Pubkey, authority: &Pubkey, fee_bps: u64) -> Instruction {
-    let mut data = Vec::with_capacity(16);
-    write_u64(&mut data, disc::UPDFEE);
-    write_u64(&mut data, fee_bps);
+    let data = AeX402Instruction::UpdateFee { fee_bps }.pack();
 
     Instruction {
         program_id: PROGRAM_ID,
@@ -320,10 +1171,61 @@ pub fn update_fee(pool: &Pubkey, authority: &Pubkey, fee_bps: u64) -> Instructio
     }
 }
 
+pub fn withdraw_admin_fees(
+    pool: &Pubkey,
+    vault0: &Pubkey,
+    vault1: &Pubkey,
+    dest0: &Pubkey,
+    dest1: &Pubkey,
+    authority: &Pubkey,
+    token_program: Option<&Pubkey>,
+) -> Instruction {
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: vec![
+            AccountMeta::new(*pool, false),
+            AccountMeta::new(*vault0, false),
+            AccountMeta::new(*vault1, false),
+            AccountMeta::new(*dest0, false),
+            AccountMeta::new(*dest1, false),
+            AccountMeta::new_readonly(*authority, true),
+            AccountMeta::new_readonly(*token_program.unwrap_or(&TOKEN_PROGRAM_ID), false),
+        ],
+        data: AeX402Instruction::WithdrawAdminFees.pack(),
+    }
+}
+
+/// Withdraw admin fees from an N-token pool. `vaults` and `dests` must
+/// have one entry per pool token, in pool order.
+pub fn withdraw_admin_fees_n(
+    pool: &Pubkey,
+    vaults: &[Pubkey],
+    dests: &[Pubkey],
+    authority: &Pubkey,
+    token_program: Option<&Pubkey>,
+) -> Result<Instruction, ProgramError> {
+    let n = vaults.len();
+    check_lengths(n, &[dests.len()])?;
+
+    let mut accounts = Vec::with_capacity(2 * n + 3);
+    accounts.push(AccountMeta::new(*pool, false));
+    accounts.extend(vaults.iter().map(|v| AccountMeta::new(*v, false)));
+    accounts.extend(dests.iter().map(|d| AccountMeta::new(*d, false)));
+    accounts.push(AccountMeta::new_readonly(*authority, true));
+    accounts.push(AccountMeta::new_readonly(
+        *token_program.unwrap_or(&TOKEN_PROGRAM_ID),
+        false,
+    ));
+
+    Ok(Instruction {
+        program_id: PROGRAM_ID,
+        accounts,
+        data: AeX402Instruction::WithdrawAdminFees.pack(),
+    })
+}
+
 pub fn commit_amp(pool: &Pubkey, authority: &Pubkey, target_amp: u64) -> Instruction {
-    let mut data = Vec::with_capacity(16);
-    write_u64(&mut data, disc::COMMITAMP);
-    write_u64(&mut data, target_amp);
+    let data = AeX402Instruction::CommitAmp { target_amp }.pack();
 
     Instruction {
         program_id: PROGRAM_ID,
@@ -336,10 +1238,11 @@ pub fn commit_amp(pool: &Pubkey, authority: &Pubkey, target_amp: u64) -> Instruc
 }
 
 pub fn ramp_amp(pool: &Pubkey, authority: &Pubkey, target_amp: u64, duration: i64) -> Instruction {
-    let mut data = Vec::with_capacity(24);
-    write_u64(&mut data, disc::RAMPAMP);
-    write_u64(&mut data, target_amp);
-    write_i64(&mut data, duration);
+    let data = AeX402Instruction::RampAmp {
+        target_amp,
+        duration,
+    }
+    .pack();
 
     Instruction {
         program_id: PROGRAM_ID,
@@ -358,7 +1261,7 @@ pub fn stop_ramp(pool: &Pubkey, authority: &Pubkey) -> Instruction {
             AccountMeta::new(*pool, false),
             AccountMeta::new_readonly(*authority, true),
         ],
-        data: disc::STOPRAMP.to_le_bytes().to_vec(),
+        data: AeX402Instruction::StopRamp.pack(),
     }
 }
 
@@ -374,7 +1277,7 @@ pub fn init_auth_transfer(
             AccountMeta::new_readonly(*authority, true),
             AccountMeta::new_readonly(*new_authority, false),
         ],
-        data: disc::INITAUTH.to_le_bytes().to_vec(),
+        data: AeX402Instruction::InitAuthTransfer.pack(),
     }
 }
 
@@ -385,7 +1288,7 @@ pub fn complete_auth_transfer(pool: &Pubkey, new_authority: &Pubkey) -> Instruct
             AccountMeta::new(*pool, false),
             AccountMeta::new_readonly(*new_authority, true),
         ],
-        data: disc::COMPLAUTH.to_le_bytes().to_vec(),
+        data: AeX402Instruction::CompleteAuthTransfer.pack(),
     }
 }
 
@@ -396,7 +1299,7 @@ pub fn cancel_auth_transfer(pool: &Pubkey, authority: &Pubkey) -> Instruction {
             AccountMeta::new(*pool, false),
             AccountMeta::new_readonly(*authority, true),
         ],
-        data: disc::CANCELAUTH.to_le_bytes().to_vec(),
+        data: AeX402Instruction::CancelAuthTransfer.pack(),
     }
 }
 
@@ -413,9 +1316,7 @@ pub fn stake_lp(
     amount: u64,
     token_program: Option<&Pubkey>,
 ) -> Instruction {
-    let mut data = Vec::with_capacity(16);
-    write_u64(&mut data, disc::STAKELP);
-    write_u64(&mut data, amount);
+    let data = AeX402Instruction::StakeLp { amount }.pack();
 
     Instruction {
         program_id: PROGRAM_ID,
@@ -440,9 +1341,7 @@ pub fn unstake_lp(
     amount: u64,
     token_program: Option<&Pubkey>,
 ) -> Instruction {
-    let mut data = Vec::with_capacity(16);
-    write_u64(&mut data, disc::UNSTAKELP);
-    write_u64(&mut data, amount);
+    let data = AeX402Instruction::UnstakeLp { amount }.pack();
 
     Instruction {
         program_id: PROGRAM_ID,
@@ -478,7 +1377,234 @@ pub fn claim_farm(
             AccountMeta::new_readonly(*user, true),
             AccountMeta::new_readonly(*token_program.unwrap_or(&TOKEN_PROGRAM_ID), false),
         ],
-        data: disc::CLAIMFARM.to_le_bytes().to_vec(),
+        data: AeX402Instruction::ClaimFarm.pack(),
+    }
+}
+
+pub fn create_farm(
+    farm: &Pubkey,
+    pool: &Pubkey,
+    reward_mint: &Pubkey,
+    reward_vault: &Pubkey,
+    lp_vault: &Pubkey,
+    authority: &Pubkey,
+    reward_rate: u64,
+    start_time: i64,
+    end_time: i64,
+) -> Instruction {
+    let data = AeX402Instruction::CreateFarm {
+        reward_rate,
+        start_time,
+        end_time,
+    }
+    .pack();
+
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: vec![
+            AccountMeta::new(*farm, false),
+            AccountMeta::new_readonly(*pool, false),
+            AccountMeta::new_readonly(*reward_mint, false),
+            AccountMeta::new_readonly(*reward_vault, false),
+            AccountMeta::new_readonly(*lp_vault, false),
+            AccountMeta::new(*authority, true),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ],
+        data,
+    }
+}
+
+/// Lock staked LP for `duration` seconds
+pub fn lock_lp(
+    user_position: &Pubkey,
+    farm: &Pubkey,
+    user_lp: &Pubkey,
+    lp_vault: &Pubkey,
+    user: &Pubkey,
+    amount: u64,
+    duration: i64,
+    token_program: Option<&Pubkey>,
+) -> Instruction {
+    let data = AeX402Instruction::LockLp { amount, duration }.pack();
+
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: vec![
+            AccountMeta::new(*user_position, false),
+            AccountMeta::new(*farm, false),
+            AccountMeta::new(*user_lp, false),
+            AccountMeta::new(*lp_vault, false),
+            AccountMeta::new_readonly(*user, true),
+            AccountMeta::new_readonly(*token_program.unwrap_or(&TOKEN_PROGRAM_ID), false),
+        ],
+        data,
+    }
+}
+
+/// Withdraw locked LP once `unlock_time` has passed
+pub fn claim_unlocked_lp(
+    user_position: &Pubkey,
+    farm: &Pubkey,
+    user_lp: &Pubkey,
+    lp_vault: &Pubkey,
+    user: &Pubkey,
+    token_program: Option<&Pubkey>,
+) -> Instruction {
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: vec![
+            AccountMeta::new(*user_position, false),
+            AccountMeta::new(*farm, false),
+            AccountMeta::new(*user_lp, false),
+            AccountMeta::new(*lp_vault, false),
+            AccountMeta::new_readonly(*user, true),
+            AccountMeta::new_readonly(*token_program.unwrap_or(&TOKEN_PROGRAM_ID), false),
+        ],
+        data: AeX402Instruction::ClaimUnlockedLp.pack(),
+    }
+}
+
+// ============================================================================
+// Lottery
+// ============================================================================
+
+pub fn enter_lottery(
+    lottery: &Pubkey,
+    entry: &Pubkey,
+    user_lp: &Pubkey,
+    lp_vault: &Pubkey,
+    user: &Pubkey,
+    ticket_count: u64,
+    token_program: Option<&Pubkey>,
+) -> Instruction {
+    let data = AeX402Instruction::EnterLottery { ticket_count }.pack();
+
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: vec![
+            AccountMeta::new(*lottery, false),
+            AccountMeta::new(*entry, false),
+            AccountMeta::new(*user_lp, false),
+            AccountMeta::new(*lp_vault, false),
+            AccountMeta::new(*user, true),
+            AccountMeta::new_readonly(*token_program.unwrap_or(&TOKEN_PROGRAM_ID), false),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ],
+        data,
+    }
+}
+
+pub fn draw_lottery(lottery: &Pubkey, authority: &Pubkey) -> Instruction {
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: vec![
+            AccountMeta::new(*lottery, false),
+            AccountMeta::new_readonly(*authority, true),
+            AccountMeta::new_readonly(sysvar::slot_hashes::ID, false),
+        ],
+        data: AeX402Instruction::DrawLottery.pack(),
+    }
+}
+
+/// Pay out a drawn lottery. Any `claimer` may sign; the prize always goes
+/// to `winner_lp`, the LP account of the winning entry's owner.
+pub fn claim_lottery(
+    lottery: &Pubkey,
+    entry: &Pubkey,
+    lp_vault: &Pubkey,
+    winner_lp: &Pubkey,
+    claimer: &Pubkey,
+    token_program: Option<&Pubkey>,
+) -> Instruction {
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: vec![
+            AccountMeta::new_readonly(*lottery, false),
+            AccountMeta::new(*entry, false),
+            AccountMeta::new(*lp_vault, false),
+            AccountMeta::new(*winner_lp, false),
+            AccountMeta::new_readonly(*claimer, true),
+            AccountMeta::new_readonly(*token_program.unwrap_or(&TOKEN_PROGRAM_ID), false),
+        ],
+        data: AeX402Instruction::ClaimLottery.pack(),
+    }
+}
+
+// ============================================================================
+// Registry
+// ============================================================================
+
+pub fn init_registry(registry: &Pubkey, authority: &Pubkey) -> Instruction {
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: vec![
+            AccountMeta::new(*registry, false),
+            AccountMeta::new(*authority, true),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ],
+        data: AeX402Instruction::InitRegistry.pack(),
+    }
+}
+
+pub fn register_pool(registry: &Pubkey, pool: &Pubkey, authority: &Pubkey) -> Instruction {
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: vec![
+            AccountMeta::new(*registry, false),
+            AccountMeta::new_readonly(*pool, false),
+            AccountMeta::new_readonly(*authority, true),
+        ],
+        data: AeX402Instruction::RegisterPool.pack(),
+    }
+}
+
+pub fn unregister_pool(registry: &Pubkey, pool: &Pubkey, authority: &Pubkey) -> Instruction {
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: vec![
+            AccountMeta::new(*registry, false),
+            AccountMeta::new_readonly(*pool, false),
+            AccountMeta::new_readonly(*authority, true),
+        ],
+        data: AeX402Instruction::UnregisterPool.pack(),
+    }
+}
+
+pub fn init_registry_auth_transfer(
+    registry: &Pubkey,
+    authority: &Pubkey,
+    new_authority: &Pubkey,
+) -> Instruction {
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: vec![
+            AccountMeta::new(*registry, false),
+            AccountMeta::new_readonly(*authority, true),
+            AccountMeta::new_readonly(*new_authority, false),
+        ],
+        data: AeX402Instruction::InitRegistryAuthTransfer.pack(),
+    }
+}
+
+pub fn complete_registry_auth_transfer(registry: &Pubkey, new_authority: &Pubkey) -> Instruction {
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: vec![
+            AccountMeta::new(*registry, false),
+            AccountMeta::new_readonly(*new_authority, true),
+        ],
+        data: AeX402Instruction::CompleteRegistryAuthTransfer.pack(),
+    }
+}
+
+pub fn cancel_registry_auth_transfer(registry: &Pubkey, authority: &Pubkey) -> Instruction {
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: vec![
+            AccountMeta::new(*registry, false),
+            AccountMeta::new_readonly(*authority, true),
+        ],
+        data: AeX402Instruction::CancelRegistryAuthTransfer.pack(),
     }
 }
 
@@ -487,15 +1613,455 @@ pub fn claim_farm(
 // ============================================================================
 
 pub fn get_twap(pool: &Pubkey, window: TwapWindow) -> Instruction {
-    let mut data = Vec::with_capacity(9);
-    write_u64(&mut data, disc::GETTWAP);
-    write_u8(&mut data, window as u8);
+    let data = AeX402Instruction::GetTwap { window }.pack();
 
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: vec![AccountMeta::new_readonly(*pool, false)],
+        data,
+    }
+}
+
+// ============================================================================
+// Transfer Hook
+// ============================================================================
+
+/// Initialize the extra-account-meta list of a Token-2022 mint whose
+/// transfer hook is this program
+pub fn init_transfer_hook(
+    extra_account_metas: &Pubkey,
+    mint: &Pubkey,
+    authority: &Pubkey,
+) -> Instruction {
     Instruction {
         program_id: PROGRAM_ID,
         accounts: vec![
-            AccountMeta::new_readonly(*pool, false),
+            AccountMeta::new(*extra_account_metas, false),
+            AccountMeta::new_readonly(*mint, false),
+            AccountMeta::new(*authority, true),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ],
+        data: AeX402Instruction::InitTransferHook.pack(),
+    }
+}
+
+/// Transfer hook execute, as invoked by Token-2022 during a transfer
+pub fn execute_transfer_hook(
+    source: &Pubkey,
+    mint: &Pubkey,
+    destination: &Pubkey,
+    owner: &Pubkey,
+    extra_account_metas: &Pubkey,
+    amount: u64,
+) -> Instruction {
+    let data = AeX402Instruction::ExecuteTransferHook { amount }.pack();
+
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: vec![
+            AccountMeta::new_readonly(*source, false),
+            AccountMeta::new_readonly(*mint, false),
+            AccountMeta::new_readonly(*destination, false),
+            AccountMeta::new_readonly(*owner, false),
+            AccountMeta::new_readonly(*extra_account_metas, false),
         ],
         data,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn all_variants() -> Vec<AeX402Instruction> {
+        use AeX402Instruction::*;
+
+        vec![
+            CreatePool {
+                amp: 1000,
+                bump: 254,
+            },
+            CreateNPool {
+                amp: 500,
+                n_tokens: 3,
+                bump: 7,
+            },
+            InitT0Vault,
+            InitT1Vault,
+            InitLpMint,
+            Swap {
+                from: 0,
+                to: 1,
+                amount_in: 1_000_000,
+                min_out: 990_000,
+                deadline: -1,
+            },
+            SwapT0T1 {
+                amount_in: 1,
+                min_out: 2,
+            },
+            SwapT1T0 {
+                amount_in: 3,
+                min_out: 4,
+            },
+            SwapN {
+                from: 2,
+                to: 5,
+                amount_in: 5,
+                min_out: 6,
+                deadline: i64::MAX,
+            },
+            MigrateT0T1 {
+                amount_in: 7,
+                min_out: 8,
+            },
+            MigrateT1T0 {
+                amount_in: 9,
+                min_out: 10,
+            },
+            AddLiquidity {
+                amount0: 11,
+                amount1: 12,
+                min_lp: 13,
+            },
+            AddLiquiditySingle {
+                index: 1,
+                amount: 14,
+                min_lp: 15,
+            },
+            AddLiquidityN {
+                amounts: vec![16, 17, 18, 19],
+                min_lp: 20,
+            },
+            RemoveLiquidity {
+                lp_amount: 21,
+                min0: 22,
+                min1: 23,
+            },
+            RemoveLiquidityN {
+                lp_amount: 24,
+                mins: vec![25, 26],
+            },
+            SetPause { paused: true },
+            SetPause { paused: false },
+            UpdateFee { fee_bps: 30 },
+            WithdrawAdminFees,
+            CommitAmp { target_amp: 2000 },
+            RampAmp {
+                target_amp: 3000,
+                duration: 86_400,
+            },
+            StopRamp,
+            InitAuthTransfer,
+            CompleteAuthTransfer,
+            CancelAuthTransfer,
+            CreateFarm {
+                reward_rate: 27,
+                start_time: 28,
+                end_time: 29,
+            },
+            StakeLp { amount: 30 },
+            UnstakeLp { amount: 31 },
+            ClaimFarm,
+            LockLp {
+                amount: 32,
+                duration: 33,
+            },
+            ClaimUnlockedLp,
+            EnterLottery { ticket_count: 34 },
+            DrawLottery,
+            ClaimLottery,
+            InitRegistry,
+            RegisterPool,
+            UnregisterPool,
+            InitRegistryAuthTransfer,
+            CompleteRegistryAuthTransfer,
+            CancelRegistryAuthTransfer,
+            GetTwap {
+                window: TwapWindow::Day7,
+            },
+            InitTransferHook,
+            ExecuteTransferHook { amount: 35 },
+        ]
+    }
+
+    #[test]
+    fn test_pack_unpack_round_trip() {
+        for ix in all_variants() {
+            let data = ix.pack();
+            assert_eq!(data[..8], ix.discriminator().to_le_bytes());
+            assert_eq!(AeX402Instruction::unpack(&data).unwrap(), ix);
+        }
+    }
+
+    #[test]
+    fn test_unpack_rejects_malformed_data() {
+        for ix in all_variants() {
+            let data = ix.pack();
+
+            let mut extra = data.clone();
+            extra.push(0);
+            assert!(AeX402Instruction::unpack(&extra).is_err(), "{:?}", ix);
+
+            if data.len() > 8 {
+                assert!(
+                    AeX402Instruction::unpack(&data[..data.len() - 1]).is_err(),
+                    "{:?}",
+                    ix
+                );
+            }
+        }
+
+        assert!(AeX402Instruction::unpack(&[]).is_err());
+        assert!(AeX402Instruction::unpack(&0u64.to_le_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_builders_decode() {
+        let key = Pubkey::new_unique();
+        let keys = [
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        ];
+
+        let ix = swap(
+            &key,
+            &key,
+            &key,
+            &key,
+            &key,
+            &key,
+            0,
+            1,
+            100,
+            90,
+            1_700_000_000,
+            None,
+        );
+        assert_eq!(
+            AeX402Instruction::unpack(&ix.data).unwrap(),
+            AeX402Instruction::Swap {
+                from: 0,
+                to: 1,
+                amount_in: 100,
+                min_out: 90,
+                deadline: 1_700_000_000,
+            }
+        );
+
+        let ix = swap_n(
+            &key,
+            &keys,
+            &key,
+            &key,
+            &key,
+            2,
+            0,
+            100,
+            90,
+            1_700_000_000,
+            None,
+        )
+        .unwrap();
+        assert_eq!(ix.accounts[1].pubkey, keys[2]);
+        assert_eq!(ix.accounts[2].pubkey, keys[0]);
+        assert_eq!(
+            AeX402Instruction::unpack(&ix.data).unwrap(),
+            AeX402Instruction::SwapN {
+                from: 2,
+                to: 0,
+                amount_in: 100,
+                min_out: 90,
+                deadline: 1_700_000_000,
+            }
+        );
+
+        // Indices must name two distinct tokens of this pool
+        assert!(swap_n(&key, &keys, &key, &key, &key, 0, 3, 100, 90, 0, None).is_err());
+        assert!(swap_n(&key, &keys, &key, &key, &key, 1, 1, 100, 90, 0, None).is_err());
+        assert!(swap_n(&key, &keys[..1], &key, &key, &key, 0, 1, 100, 90, 0, None).is_err());
+
+        let ix =
+            add_liquidity_n(&key, &keys, &key, &keys, &key, &key, &[1, 2, 3], 4, None).unwrap();
+        assert_eq!(
+            AeX402Instruction::unpack(&ix.data).unwrap(),
+            AeX402Instruction::AddLiquidityN {
+                amounts: vec![1, 2, 3],
+                min_lp: 4
+            }
+        );
+
+        let ix =
+            remove_liquidity_n(&key, &keys, &key, &keys, &key, &key, 5, &[6, 7, 8], None).unwrap();
+        assert_eq!(
+            AeX402Instruction::unpack(&ix.data).unwrap(),
+            AeX402Instruction::RemoveLiquidityN {
+                lp_amount: 5,
+                mins: vec![6, 7, 8]
+            }
+        );
+
+        let ix = create_npool(&key, &keys, &key, 100, 9).unwrap();
+        assert_eq!(
+            AeX402Instruction::unpack(&ix.data).unwrap(),
+            AeX402Instruction::CreateNPool {
+                amp: 100,
+                n_tokens: 3,
+                bump: 9
+            }
+        );
+    }
+
+    /// Checks each account's key, signer and writable flags, and that the
+    /// decoder gives it the expected role.
+    ///
+    /// The program's handler source is not published, so these expectations
+    /// pin the SDK's own account order and the `decoder` role table rather
+    /// than the on-chain handler.
+    fn assert_accounts(ix: &Instruction, expected: &[(&str, Pubkey, bool, bool)]) {
+        let decoded = crate::decoder::decode(ix).unwrap();
+        assert_eq!(ix.accounts.len(), expected.len());
+        for (i, &(role, key, signer, writable)) in expected.iter().enumerate() {
+            let meta = &ix.accounts[i];
+            assert_eq!(meta.pubkey, key, "{} key", role);
+            assert_eq!(meta.is_signer, signer, "{} signer", role);
+            assert_eq!(meta.is_writable, writable, "{} writable", role);
+            assert_eq!(decoded.accounts[i].role, role);
+        }
+    }
+
+    #[test]
+    fn test_farm_builder_accounts() {
+        let [farm, pool, reward_mint, reward_vault, lp_vault, authority] =
+            [(); 6].map(|_| Pubkey::new_unique());
+        let ix = create_farm(
+            &farm,
+            &pool,
+            &reward_mint,
+            &reward_vault,
+            &lp_vault,
+            &authority,
+            1_000,
+            10,
+            20,
+        );
+        assert_accounts(
+            &ix,
+            &[
+                ("farm", farm, false, true),
+                ("pool", pool, false, false),
+                ("reward_mint", reward_mint, false, false),
+                ("reward_vault", reward_vault, false, false),
+                ("lp_vault", lp_vault, false, false),
+                ("authority", authority, true, true),
+                ("system_program", system_program::ID, false, false),
+            ],
+        );
+
+        let [position, user_lp, user, token_program] = [(); 4].map(|_| Pubkey::new_unique());
+        let staking = |token_program| {
+            [
+                ("user_position", position, false, true),
+                ("farm", farm, false, true),
+                ("user_lp", user_lp, false, true),
+                ("lp_vault", lp_vault, false, true),
+                ("user", user, true, false),
+                ("token_program", token_program, false, false),
+            ]
+        };
+
+        let ix = lock_lp(
+            &position, &farm, &user_lp, &lp_vault, &user, 5, 86_400, None,
+        );
+        assert_accounts(&ix, &staking(TOKEN_PROGRAM_ID));
+        let ix = lock_lp(
+            &position,
+            &farm,
+            &user_lp,
+            &lp_vault,
+            &user,
+            5,
+            86_400,
+            Some(&token_program),
+        );
+        assert_accounts(&ix, &staking(token_program));
+
+        let ix = claim_unlocked_lp(&position, &farm, &user_lp, &lp_vault, &user, None);
+        assert_accounts(&ix, &staking(TOKEN_PROGRAM_ID));
+        let ix = claim_unlocked_lp(
+            &position,
+            &farm,
+            &user_lp,
+            &lp_vault,
+            &user,
+            Some(&token_program),
+        );
+        assert_accounts(&ix, &staking(token_program));
+    }
+
+    #[test]
+    fn test_withdraw_admin_fees_accounts() {
+        let [pool, vault0, vault1, dest0, dest1, authority, token_program] =
+            [(); 7].map(|_| Pubkey::new_unique());
+        let expected = |token_program| {
+            [
+                ("pool", pool, false, true),
+                ("vault0", vault0, false, true),
+                ("vault1", vault1, false, true),
+                ("dest0", dest0, false, true),
+                ("dest1", dest1, false, true),
+                ("authority", authority, true, false),
+                ("token_program", token_program, false, false),
+            ]
+        };
+
+        let ix = withdraw_admin_fees(&pool, &vault0, &vault1, &dest0, &dest1, &authority, None);
+        assert_accounts(&ix, &expected(TOKEN_PROGRAM_ID));
+        let ix = withdraw_admin_fees(
+            &pool,
+            &vault0,
+            &vault1,
+            &dest0,
+            &dest1,
+            &authority,
+            Some(&token_program),
+        );
+        assert_accounts(&ix, &expected(token_program));
+
+        // The N-token form lays out the same accounts for two tokens
+        let ix = withdraw_admin_fees_n(
+            &pool,
+            &[vault0, vault1],
+            &[dest0, dest1],
+            &authority,
+            Some(&token_program),
+        )
+        .unwrap();
+        assert_accounts(&ix, &expected(token_program));
+
+        let vaults = [(); 3].map(|_| Pubkey::new_unique());
+        let dests = [(); 3].map(|_| Pubkey::new_unique());
+        let ix = withdraw_admin_fees_n(&pool, &vaults, &dests, &authority, None).unwrap();
+        let mut expected = vec![("pool", pool, false, true)];
+        let vault_roles = ["vault0", "vault1", "vault2"];
+        let dest_roles = ["dest0", "dest1", "dest2"];
+        expected.extend(
+            vault_roles
+                .iter()
+                .zip(vaults)
+                .map(|(&r, k)| (r, k, false, true)),
+        );
+        expected.extend(
+            dest_roles
+                .iter()
+                .zip(dests)
+                .map(|(&r, k)| (r, k, false, true)),
+        );
+        expected.push(("authority", authority, true, false));
+        expected.push(("token_program", TOKEN_PROGRAM_ID, false, false));
+        assert_accounts(&ix, &expected);
+
+        assert!(withdraw_admin_fees_n(&pool, &vaults, &dests[..2], &authority, None).is_err());
+    }
+}
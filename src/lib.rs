@@ -25,13 +25,19 @@
 //! ```
 
 pub mod constants;
+pub mod decoder;
 pub mod error;
 pub mod instruction;
 pub mod math;
+pub mod parity;
+pub mod pda;
+pub mod simulator;
+pub mod solver;
 pub mod state;
+pub mod transfer_hook;
 
 pub use constants::*;
-pub use error::AeX402Error;
+pub use error::{AeX402Error, MathError};
 
 // Backwards compatibility
 pub use error::AeX402Error as StableSwapError;
@@ -1,86 +1,148 @@
 //! StableSwap math for off-chain simulation
+//!
+//! Every entry point returning `Option`, free function or `RatedBalances`
+//! method, has a `try_` twin returning `Result<_, MathError>` that says why
+//! a quote failed. The program's `MIN_SWAP` and `MIN_DEPOSIT` floors are
+//! not part of either form; `check_min_swap` and `check_min_deposit` apply
+//! them.
 
-use crate::constants::NEWTON_ITERATIONS;
+use crate::constants::{
+    MAX_TOKENS, MIGRATION_FEE_BPS, MIGRATION_FEE_DENOM, MIN_DEPOSIT, MIN_SWAP, NEWTON_ITERATIONS,
+};
+use crate::error::MathError;
+
+pub(crate) use wide::{U1024, U256, U512};
+
+/// Fixed-width integers for intermediates that outgrow u128.
+///
+/// Spot pricing needs them first: `x_j * (ann * x_i + k) * 1e18` reaches
+/// ~2^210 for u64 balances at `MAX_AMP`, and dividing early to stay in u128
+/// would round the price itself. The same type carries the invariant
+/// solvers' `*_wide` path, U512 holds exact products of up to
+/// `MAX_TOKENS` u64 amounts, and U1024 evaluates the invariant exactly
+/// when LP minting rounds D.
+#[allow(clippy::assign_op_pattern, clippy::manual_div_ceil)]
+mod wide {
+    uint::construct_uint! {
+        pub(crate) struct U256(4);
+    }
+
+    uint::construct_uint! {
+        pub(crate) struct U512(8);
+    }
+
+    uint::construct_uint! {
+        pub(crate) struct U1024(16);
+    }
+}
+
+/// Fixed-point scale for prices and virtual price (1e18)
+pub const PRICE_PRECISION: u128 = 1_000_000_000_000_000_000;
+
+/// Turns a failed checked arithmetic step into `MathError::Overflow`
+trait OrOverflow<T> {
+    fn or_overflow(self) -> Result<T, MathError>;
+}
+
+impl<T> OrOverflow<T> for Option<T> {
+    fn or_overflow(self) -> Result<T, MathError> {
+        self.ok_or(MathError::Overflow)
+    }
+}
 
 /// Calculate invariant D for 2-token pool using Newton's method
 pub fn calc_d(x: u64, y: u64, amp: u64) -> Option<u64> {
+    try_calc_d(x, y, amp).ok()
+}
+
+/// `calc_d` with a typed error
+pub fn try_calc_d(x: u64, y: u64, amp: u64) -> Result<u64, MathError> {
     // Guard against division by zero
     if x == 0 || y == 0 {
-        return Some(0);
+        return Ok(0);
     }
 
-    let s = x.checked_add(y)?;
+    let s = x.checked_add(y).or_overflow()?;
     if s == 0 {
-        return Some(0);
+        return Ok(0);
     }
 
     let mut d = s;
-    let ann = amp.checked_mul(4)?; // A * n^n where n=2
+    let ann = amp.checked_mul(4).or_overflow()?; // A * n^n where n=2
 
     // Guard against zero amp
     if ann == 0 {
-        return None;
+        return Err(MathError::ZeroAmp);
     }
 
     for _ in 0..NEWTON_ITERATIONS {
         // d_p = d^3 / (4 * x * y)
         let d_p = (d as u128)
-            .checked_mul(d as u128)?
-            .checked_div(x.checked_mul(2)? as u128)?
-            .checked_mul(d as u128)?
-            .checked_div(y.checked_mul(2)? as u128)?;
+            .checked_mul(d as u128)
+            .and_then(|v| v.checked_div(x.checked_mul(2)? as u128))
+            .and_then(|v| v.checked_mul(d as u128))
+            .and_then(|v| v.checked_div(y.checked_mul(2)? as u128))
+            .or_overflow()?;
 
         let d_prev = d;
 
         // d = (ann * s + d_p * 2) * d / ((ann - 1) * d + 3 * d_p)
         let num = (ann as u128)
-            .checked_mul(s as u128)?
-            .checked_add(d_p.checked_mul(2)?)?
-            .checked_mul(d as u128)?;
-        
-        let denom = (ann.checked_sub(1)? as u128)
-            .checked_mul(d as u128)?
-            .checked_add(d_p.checked_mul(3)?)?;
+            .checked_mul(s as u128)
+            .and_then(|v| v.checked_add(d_p.checked_mul(2)?))
+            .and_then(|v| v.checked_mul(d as u128))
+            .or_overflow()?;
+
+        let denom = ((ann - 1) as u128)
+            .checked_mul(d as u128)
+            .and_then(|v| v.checked_add(d_p.checked_mul(3)?))
+            .or_overflow()?;
 
         // Guard against division by zero
         if denom == 0 {
-            return None;
+            return Err(MathError::NonConvergence);
         }
-        d = (num / denom) as u64;
+        d = u64::try_from(num / denom).ok().or_overflow()?;
 
         // Check convergence
-        let diff = if d > d_prev { d - d_prev } else { d_prev - d };
+        let diff = d.abs_diff(d_prev);
         if diff <= 1 {
-            return Some(d);
+            return Ok(d);
         }
     }
 
-    None // Failed to converge
+    Err(MathError::NonConvergence)
 }
 
 /// Calculate output amount y given input x for swap
 pub fn calc_y(x_new: u64, d: u64, amp: u64) -> Option<u64> {
+    try_calc_y(x_new, d, amp).ok()
+}
+
+/// `calc_y` with a typed error
+pub fn try_calc_y(x_new: u64, d: u64, amp: u64) -> Result<u64, MathError> {
     // Guard against division by zero
     if x_new == 0 {
-        return None;
+        return Err(MathError::ZeroBalance);
     }
 
-    let ann = amp.checked_mul(4)?;
+    let ann = amp.checked_mul(4).or_overflow()?;
 
     // Guard against zero amp
     if ann == 0 {
-        return None;
+        return Err(MathError::ZeroAmp);
     }
 
     // c = d^3 / (4 * x_new * ann)
     let c = (d as u128)
-        .checked_mul(d as u128)?
-        .checked_div(x_new.checked_mul(2)? as u128)?
-        .checked_mul(d as u128)?
-        .checked_div(ann.checked_mul(2)? as u128)?;
+        .checked_mul(d as u128)
+        .and_then(|v| v.checked_div(x_new.checked_mul(2)? as u128))
+        .and_then(|v| v.checked_mul(d as u128))
+        .and_then(|v| v.checked_div(ann.checked_mul(2)? as u128))
+        .or_overflow()?;
 
     // b = x_new + d / ann
-    let b = x_new.checked_add(d / ann)?;
+    let b = x_new.checked_add(d / ann).or_overflow()?;
 
     let mut y = d;
 
@@ -89,28 +151,155 @@ pub fn calc_y(x_new: u64, d: u64, amp: u64) -> Option<u64> {
 
         // y = (y^2 + c) / (2y + b - d)
         let num = (y as u128)
-            .checked_mul(y as u128)?
-            .checked_add(c)?;
-        
+            .checked_mul(y as u128)
+            .and_then(|v| v.checked_add(c))
+            .or_overflow()?;
+
         let denom = y
-            .checked_mul(2)?
-            .checked_add(b)?
-            .checked_sub(d)?;
+            .checked_mul(2)
+            .and_then(|v| v.checked_add(b))
+            .and_then(|v| v.checked_sub(d))
+            .or_overflow()?;
 
         // Guard against division by zero
         if denom == 0 {
-            return None;
+            return Err(MathError::NonConvergence);
         }
-        y = (num / denom as u128) as u64;
+        y = u64::try_from(num / denom as u128).ok().or_overflow()?;
 
         // Check convergence
-        let diff = if y > y_prev { y - y_prev } else { y_prev - y };
+        let diff = y.abs_diff(y_prev);
         if diff <= 1 {
-            return Some(y);
+            return Ok(y);
+        }
+    }
+
+    Err(MathError::NonConvergence)
+}
+
+/// Calculate invariant D for a 2-token pool in 256-bit arithmetic.
+///
+/// Performs the same integer steps as `calc_d`, so the two agree wherever
+/// `calc_d` succeeds, but intermediates cannot overflow and D may exceed
+/// `u64::MAX`.
+pub fn calc_d_wide(x: u64, y: u64, amp: u64) -> Result<u128, MathError> {
+    // Guard against division by zero
+    if x == 0 || y == 0 {
+        return Ok(0);
+    }
+
+    let s = U256::from(x) + U256::from(y);
+    let ann = U256::from(amp) * U256::from(4); // A * n^n where n=2
+
+    // Guard against zero amp
+    if ann.is_zero() {
+        return Err(MathError::ZeroAmp);
+    }
+
+    let (x2, y2) = (U256::from(x) * 2, U256::from(y) * 2);
+    let mut d = s;
+
+    for _ in 0..NEWTON_ITERATIONS {
+        // d_p = d^3 / (4 * x * y)
+        let d_p = d
+            .checked_mul(d)
+            .map(|v| v / x2)
+            .and_then(|v| v.checked_mul(d))
+            .map(|v| v / y2)
+            .or_overflow()?;
+
+        let d_prev = d;
+
+        // d = (ann * s + d_p * 2) * d / ((ann - 1) * d + 3 * d_p)
+        let num = ann
+            .checked_mul(s)
+            .and_then(|v| v.checked_add(d_p.checked_mul(U256::from(2))?))
+            .and_then(|v| v.checked_mul(d))
+            .or_overflow()?;
+
+        let denom = (ann - 1)
+            .checked_mul(d)
+            .and_then(|v| v.checked_add(d_p.checked_mul(U256::from(3))?))
+            .or_overflow()?;
+
+        // Guard against division by zero
+        if denom.is_zero() {
+            return Err(MathError::NonConvergence);
+        }
+        d = num / denom;
+
+        // Check convergence
+        let diff = if d > d_prev { d - d_prev } else { d_prev - d };
+        if diff <= U256::one() {
+            return u128::try_from(d).ok().or_overflow();
+        }
+    }
+
+    Err(MathError::NonConvergence)
+}
+
+/// Calculate output balance y given input x in 256-bit arithmetic, for an
+/// invariant from `calc_d_wide`.
+///
+/// Performs the same integer steps as `calc_y`; fails with `Overflow` only
+/// if the resulting balance itself does not fit in a `u64`.
+pub fn calc_y_wide(x_new: u64, d: u128, amp: u64) -> Result<u64, MathError> {
+    // Guard against division by zero
+    if x_new == 0 {
+        return Err(MathError::ZeroBalance);
+    }
+
+    let ann = U256::from(amp) * U256::from(4);
+
+    // Guard against zero amp
+    if ann.is_zero() {
+        return Err(MathError::ZeroAmp);
+    }
+
+    let d = U256::from(d);
+
+    // c = d^3 / (4 * x_new * ann)
+    let c = d
+        .checked_mul(d)
+        .map(|v| v / (U256::from(x_new) * 2))
+        .and_then(|v| v.checked_mul(d))
+        .map(|v| v / (ann * 2))
+        .or_overflow()?;
+
+    // b = x_new + d / ann
+    let b = U256::from(x_new) + d / ann;
+
+    let mut y = d;
+
+    for _ in 0..NEWTON_ITERATIONS {
+        let y_prev = y;
+
+        // y = (y^2 + c) / (2y + b - d)
+        let num = y
+            .checked_mul(y)
+            .and_then(|v| v.checked_add(c))
+            .or_overflow()?;
+
+        let denom = y
+            .checked_mul(U256::from(2))
+            .and_then(|v| v.checked_add(b))
+            .and_then(|v| v.checked_sub(d))
+            .or_overflow()?;
+
+        // Guard against division by zero
+        if denom.is_zero() {
+            return Err(MathError::NonConvergence);
+        }
+        y = num / denom;
+
+        // Check convergence
+        let diff = if y > y_prev { y - y_prev } else { y_prev - y };
+        if diff <= U256::one() {
+            return u64::try_from(y).ok().or_overflow();
         }
     }
 
-    None
+    Err(MathError::NonConvergence)
 }
 
 /// Simulate a swap and return output amount
@@ -121,16 +310,227 @@ pub fn simulate_swap(
     amp: u64,
     fee_bps: u64,
 ) -> Option<u64> {
-    let d = calc_d(bal_in, bal_out, amp)?;
-    let new_bal_in = bal_in.checked_add(amount_in)?;
-    let new_bal_out = calc_y(new_bal_in, d, amp)?;
-    let mut amount_out = bal_out.checked_sub(new_bal_out)?;
+    swap_out_after_fee(bal_in, bal_out, amount_in, amp, fee_bps).ok()
+}
+
+/// `simulate_swap` with a typed error
+pub fn try_simulate_swap(
+    bal_in: u64,
+    bal_out: u64,
+    amount_in: u64,
+    amp: u64,
+    fee_bps: u64,
+) -> Result<u64, MathError> {
+    swap_out_after_fee(bal_in, bal_out, amount_in, amp, fee_bps)
+}
+
+/// Reject a non-zero deposit `amount` below `MIN_DEPOSIT`.
+///
+/// Like `check_min_swap`, quotes do not apply this floor; call it for each
+/// token deposited before building a deposit instruction.
+pub fn check_min_deposit(amount: u64) -> Result<(), MathError> {
+    if amount != 0 && amount < MIN_DEPOSIT {
+        return Err(MathError::BelowMinDeposit);
+    }
+    Ok(())
+}
+
+/// Reject `amount_in` below `MIN_SWAP`, as the program's swap and
+/// migration handlers do.
+///
+/// Quotes do not apply this check, so they can price trades of any size;
+/// call it before building a swap instruction.
+pub fn check_min_swap(amount_in: u64) -> Result<(), MathError> {
+    if amount_in < MIN_SWAP {
+        return Err(MathError::BelowMinSwap);
+    }
+    Ok(())
+}
+
+fn swap_out_after_fee(
+    bal_in: u64,
+    bal_out: u64,
+    amount_in: u64,
+    amp: u64,
+    fee_bps: u64,
+) -> Result<u64, MathError> {
+    let amount_out = calc_swap_out(bal_in, bal_out, amount_in, amp)?;
+    apply_fee(amount_out, fee_bps)
+}
+
+/// Deduct `fee_bps` from `amount_out`
+fn apply_fee(amount_out: u64, fee_bps: u64) -> Result<u64, MathError> {
+    let fee = amount_out.checked_mul(fee_bps).or_overflow()? / 10000;
+    amount_out.checked_sub(fee).or_overflow()
+}
+
+/// Calculate the minimum `amount_in` that makes `simulate_swap` return at
+/// least `amount_out`.
+///
+/// The curve is inverted to get an estimate, which is then refined against
+/// `simulate_swap` itself, so rounding always falls against the user and
+/// the swap never under-delivers.
+pub fn simulate_swap_exact_out(
+    bal_in: u64,
+    bal_out: u64,
+    amount_out: u64,
+    amp: u64,
+    fee_bps: u64,
+) -> Option<u64> {
+    try_simulate_swap_exact_out(bal_in, bal_out, amount_out, amp, fee_bps).ok()
+}
+
+/// `simulate_swap_exact_out` with a typed error
+pub fn try_simulate_swap_exact_out(
+    bal_in: u64,
+    bal_out: u64,
+    amount_out: u64,
+    amp: u64,
+    fee_bps: u64,
+) -> Result<u64, MathError> {
+    if amount_out == 0 {
+        return Ok(0);
+    }
+
+    let gross_out = gross_up_fee(amount_out, fee_bps)?;
+    let d = try_calc_d(bal_in, bal_out, amp)?;
+    let new_bal_out = bal_out
+        .checked_sub(gross_out)
+        .filter(|&b| b > 0)
+        .ok_or(MathError::InsufficientLiquidity)?;
+    let new_bal_in = try_calc_y(new_bal_out, d, amp)?;
+    let estimate = new_bal_in.saturating_sub(bal_in);
+
+    min_amount_in(amount_out, estimate, |amount_in| {
+        simulate_swap(bal_in, bal_out, amount_in, amp, fee_bps)
+    })
+}
+
+/// Calculate the minimum `amount_in` of token `i` that makes
+/// `simulate_swap_n` return at least `amount_out` of token `j`
+pub fn simulate_swap_exact_out_n(
+    balances: &[u64],
+    i: usize,
+    j: usize,
+    amount_out: u64,
+    amp: u64,
+    fee_bps: u64,
+) -> Option<u64> {
+    try_simulate_swap_exact_out_n(balances, i, j, amount_out, amp, fee_bps).ok()
+}
+
+/// `simulate_swap_exact_out_n` with a typed error
+pub fn try_simulate_swap_exact_out_n(
+    balances: &[u64],
+    i: usize,
+    j: usize,
+    amount_out: u64,
+    amp: u64,
+    fee_bps: u64,
+) -> Result<u64, MathError> {
+    if amount_out == 0 {
+        return Ok(0);
+    }
+
+    let gross_out = gross_up_fee(amount_out, fee_bps)?;
+    let new_bal_out = balances
+        .get(j)
+        .ok_or(MathError::InvalidToken)?
+        .checked_sub(gross_out)
+        .filter(|&b| b > 0)
+        .ok_or(MathError::InsufficientLiquidity)?;
+    let new_bal_in = try_calc_y_n(balances, j, i, new_bal_out, amp)?;
+    let estimate = new_bal_in.saturating_sub(balances[i]);
+
+    min_amount_in(amount_out, estimate, |amount_in| {
+        simulate_swap_n(balances, i, j, amount_in, amp, fee_bps)
+    })
+}
+
+/// Output before fee needed to net `amount_out` after `fee_bps`, rounded up
+fn gross_up_fee(amount_out: u64, fee_bps: u64) -> Result<u64, MathError> {
+    let keep = 10000u64
+        .checked_sub(fee_bps)
+        .filter(|&k| k > 0)
+        .or_overflow()?;
+    let gross = (amount_out as u128 * 10000).div_ceil(keep as u128);
+    u64::try_from(gross).ok().or_overflow()
+}
+
+/// Smallest input for which `quote` reaches `target`, searching outward
+/// from `estimate`. `quote` must be non-decreasing in its input.
+fn min_amount_in<F>(target: u64, estimate: u64, quote: F) -> Result<u64, MathError>
+where
+    F: Fn(u64) -> Option<u64>,
+{
+    let reaches = |amount_in: u64| quote(amount_in).is_some_and(|out| out >= target);
+
+    // Upper bound: grow from the estimate until the target is reached
+    let mut hi = estimate.max(1);
+    while !reaches(hi) {
+        hi = hi.checked_mul(2).ok_or(MathError::InsufficientLiquidity)?;
+    }
+
+    // Lower bound: a bit under the estimate, else zero
+    let mut lo = hi.saturating_sub(hi / 1000 + 16);
+    if reaches(lo) {
+        lo = 0;
+    }
+
+    // Invariant: !reaches(lo) && reaches(hi)
+    while hi - lo > 1 {
+        let mid = lo + (hi - lo) / 2;
+        if reaches(mid) {
+            hi = mid;
+        } else {
+            lo = mid;
+        }
+    }
+
+    Ok(hi)
+}
+
+/// Simulate a migration swap (MIGT0T1 / MIGT1T0) and return output amount.
+///
+/// Migrations pay `MIGRATION_FEE_BPS` (0.1337%) instead of the pool's
+/// `fee_bps`.
+pub fn simulate_migration(bal_in: u64, bal_out: u64, amount_in: u64, amp: u64) -> Option<u64> {
+    migration_out(bal_in, bal_out, amount_in, amp).ok()
+}
+
+/// `simulate_migration` with a typed error
+pub fn try_simulate_migration(
+    bal_in: u64,
+    bal_out: u64,
+    amount_in: u64,
+    amp: u64,
+) -> Result<u64, MathError> {
+    migration_out(bal_in, bal_out, amount_in, amp)
+}
+
+fn migration_out(bal_in: u64, bal_out: u64, amount_in: u64, amp: u64) -> Result<u64, MathError> {
+    let amount_out = calc_swap_out(bal_in, bal_out, amount_in, amp)?;
+
+    let fee = (amount_out as u128)
+        .checked_mul(MIGRATION_FEE_BPS as u128)
+        .or_overflow()?
+        / MIGRATION_FEE_DENOM as u128;
+
+    amount_out.checked_sub(fee as u64).or_overflow()
+}
 
-    // Apply fee
-    let fee = amount_out.checked_mul(fee_bps)? / 10000;
-    amount_out = amount_out.checked_sub(fee)?;
+/// Curve output for `amount_in` before any fee
+fn calc_swap_out(bal_in: u64, bal_out: u64, amount_in: u64, amp: u64) -> Result<u64, MathError> {
+    if bal_in == 0 || bal_out == 0 {
+        return Err(MathError::ZeroBalance);
+    }
 
-    Some(amount_out)
+    let d = try_calc_d(bal_in, bal_out, amp)?;
+    let new_bal_in = bal_in.checked_add(amount_in).or_overflow()?;
+    let new_bal_out = try_calc_y(new_bal_in, d, amp)?;
+    bal_out
+        .checked_sub(new_bal_out)
+        .ok_or(MathError::InsufficientLiquidity)
 }
 
 /// Calculate LP tokens for deposit (2-token pool)
@@ -142,140 +542,1575 @@ pub fn calc_lp_tokens(
     lp_supply: u64,
     amp: u64,
 ) -> Option<u64> {
+    try_calc_lp_tokens(amt0, amt1, bal0, bal1, lp_supply, amp).ok()
+}
+
+/// `calc_lp_tokens` with a typed error
+pub fn try_calc_lp_tokens(
+    amt0: u64,
+    amt1: u64,
+    bal0: u64,
+    bal1: u64,
+    lp_supply: u64,
+    amp: u64,
+) -> Result<u64, MathError> {
     if lp_supply == 0 {
         // Initial deposit: LP = sqrt(amt0 * amt1)
-        let product = (amt0 as u128).checked_mul(amt1 as u128)?;
-        return Some(isqrt(product) as u64);
+        let product = (amt0 as u128).checked_mul(amt1 as u128).or_overflow()?;
+        return Ok(isqrt(product) as u64);
     }
 
-    let d0 = calc_d(bal0, bal1, amp)?;
-    let d1 = calc_d(bal0.checked_add(amt0)?, bal1.checked_add(amt1)?, amp)?;
+    let d0 = try_calc_d(bal0, bal1, amp)?;
+    let new0 = bal0.checked_add(amt0).or_overflow()?;
+    let new1 = bal1.checked_add(amt1).or_overflow()?;
+    let d1 = try_calc_d(new0, new1, amp)?;
 
     if d0 == 0 {
-        return None;
+        return Err(MathError::ZeroBalance);
     }
 
-    // LP tokens = lp_supply * (d1 - d0) / d0
-    let lp = (lp_supply as u128)
-        .checked_mul(d1.checked_sub(d0)? as u128)?
-        / d0 as u128;
+    let lp = mint_lp(lp_supply, &[bal0, bal1], d0, &[new0, new1], d1, amp)?;
 
-    Some(lp as u64)
+    u64::try_from(lp).ok().or_overflow()
 }
 
-/// Calculate tokens received for LP burn
-pub fn calc_withdraw(
-    lp_amount: u64,
+/// Calculate LP tokens for deposit, charging the StableSwap imbalance fee.
+///
+/// Each side pays `fee_bps * n / (4 * (n - 1))` on the distance between
+/// its new balance and the balance a perfectly proportional deposit would
+/// have produced, so lopsided deposits mint less LP than `calc_lp_tokens`.
+pub fn calc_lp_tokens_imbalanced(
+    amt0: u64,
+    amt1: u64,
     bal0: u64,
     bal1: u64,
     lp_supply: u64,
-) -> Option<(u64, u64)> {
-    if lp_supply == 0 {
-        return None;
-    }
-
-    let amount0 = (bal0 as u128)
-        .checked_mul(lp_amount as u128)?
-        / lp_supply as u128;
-    
-    let amount1 = (bal1 as u128)
-        .checked_mul(lp_amount as u128)?
-        / lp_supply as u128;
-
-    Some((amount0 as u64, amount1 as u64))
+    amp: u64,
+    fee_bps: u64,
+) -> Option<u64> {
+    try_calc_lp_tokens_imbalanced(amt0, amt1, bal0, bal1, lp_supply, amp, fee_bps).ok()
 }
 
-/// Calculate current amp during ramping
-pub fn get_current_amp(
+/// `calc_lp_tokens_imbalanced` with a typed error
+pub fn try_calc_lp_tokens_imbalanced(
+    amt0: u64,
+    amt1: u64,
+    bal0: u64,
+    bal1: u64,
+    lp_supply: u64,
     amp: u64,
-    target_amp: u64,
-    ramp_start: i64,
-    ramp_end: i64,
-    now: i64,
-) -> u64 {
-    if now >= ramp_end || ramp_end == ramp_start {
-        return target_amp;
+    fee_bps: u64,
+) -> Result<u64, MathError> {
+    if lp_supply == 0 {
+        return try_calc_lp_tokens(amt0, amt1, bal0, bal1, lp_supply, amp);
     }
 
-    if now <= ramp_start {
-        return amp;
+    let d0 = try_calc_d(bal0, bal1, amp)?;
+    if d0 == 0 {
+        return Err(MathError::ZeroBalance);
     }
 
-    let elapsed = (now - ramp_start) as u64;
-    let duration = (ramp_end - ramp_start) as u64;
-
-    if target_amp > amp {
-        let diff = target_amp - amp;
-        amp + (diff * elapsed) / duration
-    } else {
-        let diff = amp - target_amp;
-        amp - (diff * elapsed) / duration
+    let new0 = bal0.checked_add(amt0).or_overflow()?;
+    let new1 = bal1.checked_add(amt1).or_overflow()?;
+    let d1 = try_calc_d(new0, new1, amp)?;
+    if d1 <= d0 {
+        return Err(MathError::InsufficientLiquidity);
     }
-}
 
-/// Calculate price impact for a swap
-pub fn calc_price_impact(
-    bal_in: u64,
-    bal_out: u64,
-    amount_in: u64,
-    amp: u64,
-    fee_bps: u64,
-) -> Option<f64> {
-    // Guard against division by zero
-    if amount_in == 0 {
-        return None;
+    let mut adjusted = [new0, new1];
+    for (adj, old) in adjusted.iter_mut().zip([bal0, bal1]) {
+        let ideal = (d1 as u128).checked_mul(old as u128).or_overflow()? / d0 as u128;
+        let diff = ideal.abs_diff(*adj as u128);
+        let fee = imbalance_fee(diff, fee_bps, 2)?;
+        *adj = adj.checked_sub(fee).or_overflow()?;
     }
 
-    let amount_out = simulate_swap(bal_in, bal_out, amount_in, amp, fee_bps)?;
+    let d2 = try_calc_d(adjusted[0], adjusted[1], amp)?;
+    let lp = mint_lp(lp_supply, &[bal0, bal1], d0, &adjusted, d2, amp)?;
 
-    // Price impact = 1 - (amount_out / amount_in)
-    let ratio = (amount_out as f64) / (amount_in as f64);
-    Some(1.0 - ratio)
+    u64::try_from(lp).ok().or_overflow()
 }
 
-/// Calculate minimum output with slippage tolerance
-pub fn calc_min_output(expected: u64, slippage_bps: u64) -> u64 {
-    let slippage = expected.saturating_mul(slippage_bps) / 10000;
-    expected.saturating_sub(slippage)
+/// Calculate LP tokens for a single-sided deposit of token `index` (ADDLIQ1)
+pub fn calc_lp_tokens_single(
+    amount: u64,
+    index: u8,
+    bal0: u64,
+    bal1: u64,
+    lp_supply: u64,
+    amp: u64,
+    fee_bps: u64,
+) -> Option<u64> {
+    try_calc_lp_tokens_single(amount, index, bal0, bal1, lp_supply, amp, fee_bps).ok()
 }
 
-/// Calculate virtual price (LP value relative to underlying)
-pub fn calc_virtual_price(bal0: u64, bal1: u64, lp_supply: u64, amp: u64) -> Option<u128> {
+/// `calc_lp_tokens_single` with a typed error
+pub fn try_calc_lp_tokens_single(
+    amount: u64,
+    index: u8,
+    bal0: u64,
+    bal1: u64,
+    lp_supply: u64,
+    amp: u64,
+    fee_bps: u64,
+) -> Result<u64, MathError> {
+    // A one-sided deposit cannot seed an empty pool
     if lp_supply == 0 {
-        return None;
+        return Err(MathError::InsufficientLiquidity);
     }
 
-    let d = calc_d(bal0, bal1, amp)?;
-    
-    // Virtual price = D * 1e18 / lp_supply
-    const PRECISION: u128 = 1_000_000_000_000_000_000; // 1e18
-    Some((d as u128).checked_mul(PRECISION)? / lp_supply as u128)
+    match index {
+        0 => try_calc_lp_tokens_imbalanced(amount, 0, bal0, bal1, lp_supply, amp, fee_bps),
+        1 => try_calc_lp_tokens_imbalanced(0, amount, bal0, bal1, lp_supply, amp, fee_bps),
+        _ => Err(MathError::InvalidToken),
+    }
 }
 
-/// Integer square root using Newton's method
-fn isqrt(n: u128) -> u128 {
-    if n == 0 {
-        return 0;
-    }
-    if n <= 3 {
-        return 1;
-    }
+/// Imbalance fee on `diff`: `diff * fee_bps * n / (4 * (n - 1) * 10000)`
+fn imbalance_fee(diff: u128, fee_bps: u64, n: u64) -> Result<u64, MathError> {
+    let fee = diff
+        .checked_mul(fee_bps as u128)
+        .and_then(|v| v.checked_mul(n as u128))
+        .or_overflow()?
+        / (4 * (n as u128 - 1) * 10000);
+    u64::try_from(fee).ok().or_overflow()
+}
 
-    let mut x = n;
-    let mut y = (x + 1) / 2;
+/// Value `amount0` of token0 plus `amount1` of token1, in token1 units.
+///
+/// token0 is priced at `spot_price`, so the result ignores the slippage an
+/// actual swap of `amount0` would incur.
+pub fn calc_value_in_token1(
+    amount0: u64,
+    amount1: u64,
+    bal0: u64,
+    bal1: u64,
+    amp: u64,
+) -> Option<u64> {
+    try_calc_value_in_token1(amount0, amount1, bal0, bal1, amp).ok()
+}
 
-    while y < x {
-        x = y;
-        y = (x + n / x) / 2;
+/// `calc_value_in_token1` with a typed error
+pub fn try_calc_value_in_token1(
+    amount0: u64,
+    amount1: u64,
+    bal0: u64,
+    bal1: u64,
+    amp: u64,
+) -> Result<u64, MathError> {
+    if amount0 == 0 {
+        return Ok(amount1);
     }
 
-    x
+    let price = try_spot_price(bal0, bal1, amp)?;
+    let value0 = U256::from(amount0)
+        .checked_mul(U256::from(price))
+        .or_overflow()?
+        / U256::from(PRICE_PRECISION);
+    u64::try_from(value0)
+        .ok()
+        .and_then(|v| v.checked_add(amount1))
+        .or_overflow()
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-
+/// Value `amounts` (one per pool token) in token `numeraire` at the
+/// pool's spot prices (N-token pool).
+///
+/// For two tokens valued in token 1 this matches `calc_value_in_token1`.
+pub fn calc_value_in_token_n(
+    amounts: &[u64],
+    balances: &[u64],
+    numeraire: usize,
+    amp: u64,
+) -> Option<u64> {
+    try_calc_value_in_token_n(amounts, balances, numeraire, amp).ok()
+}
+
+/// `calc_value_in_token_n` with a typed error
+pub fn try_calc_value_in_token_n(
+    amounts: &[u64],
+    balances: &[u64],
+    numeraire: usize,
+    amp: u64,
+) -> Result<u64, MathError> {
+    if amounts.len() != balances.len() || numeraire >= balances.len() {
+        return Err(MathError::InvalidToken);
+    }
+
+    amounts
+        .iter()
+        .enumerate()
+        .try_fold(0u64, |total, (k, &amount)| {
+            let value = if k == numeraire || amount == 0 {
+                amount
+            } else {
+                let price = try_spot_price_n(balances, k, numeraire, amp)?;
+                let value = U256::from(amount)
+                    .checked_mul(U256::from(price))
+                    .or_overflow()?
+                    / U256::from(PRICE_PRECISION);
+                u64::try_from(value).ok().or_overflow()?
+            };
+            total.checked_add(value).or_overflow()
+        })
+}
+
+/// Calculate tokens received for LP burn
+pub fn calc_withdraw(lp_amount: u64, bal0: u64, bal1: u64, lp_supply: u64) -> Option<(u64, u64)> {
+    try_calc_withdraw(lp_amount, bal0, bal1, lp_supply).ok()
+}
+
+/// `calc_withdraw` with a typed error
+pub fn try_calc_withdraw(
+    lp_amount: u64,
+    bal0: u64,
+    bal1: u64,
+    lp_supply: u64,
+) -> Result<(u64, u64), MathError> {
+    if lp_supply == 0 {
+        return Err(MathError::InsufficientLiquidity);
+    }
+
+    let amount0 = (bal0 as u128)
+        .checked_mul(lp_amount as u128)
+        .or_overflow()?
+        / lp_supply as u128;
+
+    let amount1 = (bal1 as u128)
+        .checked_mul(lp_amount as u128)
+        .or_overflow()?
+        / lp_supply as u128;
+
+    Ok((amount0 as u64, amount1 as u64))
+}
+
+/// Calculate tokens received for burning `lp_amount` into token `index`
+/// only (works for 2-token and N-token pools).
+///
+/// Returns `(amount_out, fee)`, where `fee` is the total cost of exiting
+/// one-sided: the imbalance fee charged on every balance plus rounding.
+/// Comparing `fee` against `amount_out` flags unusually costly exits.
+pub fn calc_withdraw_one(
+    lp_amount: u64,
+    index: usize,
+    balances: &[u64],
+    lp_supply: u64,
+    amp: u64,
+    fee_bps: u64,
+) -> Option<(u64, u64)> {
+    try_calc_withdraw_one(lp_amount, index, balances, lp_supply, amp, fee_bps).ok()
+}
+
+/// `calc_withdraw_one` with a typed error
+pub fn try_calc_withdraw_one(
+    lp_amount: u64,
+    index: usize,
+    balances: &[u64],
+    lp_supply: u64,
+    amp: u64,
+    fee_bps: u64,
+) -> Result<(u64, u64), MathError> {
+    let n = balances.len();
+    if index >= n {
+        return Err(MathError::InvalidToken);
+    }
+    if lp_amount >= lp_supply {
+        return Err(MathError::InsufficientLiquidity);
+    }
+
+    let d0 = try_calc_d_n(balances, amp)?;
+    if d0 == 0 {
+        return Err(MathError::ZeroBalance);
+    }
+
+    // d1 = d0 - lp_amount * d0 / lp_supply
+    let burned = (d0 as u128).checked_mul(lp_amount as u128).or_overflow()? / lp_supply as u128;
+    let d1 = d0.checked_sub(burned as u64).or_overflow()?;
+    let new_y = calc_y_d_n(balances, index, d1, amp)?;
+
+    // Charge the imbalance fee on each balance's deviation from a
+    // proportional withdrawal
+    let mut reduced = balances.to_vec();
+    for (j, bal) in reduced.iter_mut().enumerate() {
+        let proportional = (*bal as u128).checked_mul(d1 as u128).or_overflow()? / d0 as u128;
+        let expected_dx = if j == index {
+            proportional.checked_sub(new_y as u128)
+        } else {
+            (*bal as u128).checked_sub(proportional)
+        }
+        .or_overflow()?;
+        let fee = imbalance_fee(expected_dx, fee_bps, n as u64)?;
+        *bal = bal
+            .checked_sub(fee)
+            .ok_or(MathError::InsufficientLiquidity)?;
+    }
+
+    // Round down against the user
+    let dy = reduced[index]
+        .checked_sub(calc_y_d_n(&reduced, index, d1, amp)?)
+        .ok_or(MathError::InsufficientLiquidity)?
+        .saturating_sub(1);
+    let dy_no_fee = balances[index]
+        .checked_sub(new_y)
+        .ok_or(MathError::InsufficientLiquidity)?;
+
+    Ok((dy, dy_no_fee.saturating_sub(dy)))
+}
+
+/// Current amp while ramping linearly from `amp` at `ramp_start` to
+/// `target_amp` at `ramp_end`.
+///
+/// A malformed (inverted) ramp yields `amp` before `ramp_end` and
+/// `target_amp` from then on; use `try_get_current_amp` to reject it.
+pub fn get_current_amp(amp: u64, target_amp: u64, ramp_start: i64, ramp_end: i64, now: i64) -> u64 {
+    try_get_current_amp(amp, target_amp, ramp_start, ramp_end, now).unwrap_or(if now < ramp_end {
+        amp
+    } else {
+        target_amp
+    })
+}
+
+/// `get_current_amp` with a typed error.
+///
+/// Fails with `InvalidRamp` when `ramp_end` is before `ramp_start`; any
+/// other timestamps, amps and durations are valid and cannot overflow.
+pub fn try_get_current_amp(
+    amp: u64,
+    target_amp: u64,
+    ramp_start: i64,
+    ramp_end: i64,
+    now: i64,
+) -> Result<u64, MathError> {
+    if ramp_end < ramp_start {
+        return Err(MathError::InvalidRamp);
+    }
+
+    if now >= ramp_end || ramp_end == ramp_start {
+        return Ok(target_amp);
+    }
+
+    if now <= ramp_start {
+        return Ok(amp);
+    }
+
+    // Distances between any two i64s fit in u64, and elapsed < duration
+    let elapsed = now.abs_diff(ramp_start) as u128;
+    let duration = ramp_end.abs_diff(ramp_start) as u128;
+
+    // diff * elapsed / duration < diff, so the result stays between the endpoints
+    let step = |diff: u64| (diff as u128 * elapsed / duration) as u64;
+
+    if target_amp > amp {
+        Ok(amp + step(target_amp - amp))
+    } else {
+        Ok(amp - step(amp - target_amp))
+    }
+}
+
+/// Spot price of token0 in token1, scaled by `PRICE_PRECISION`.
+///
+/// This is the marginal rate `-dy/dx` along the invariant at the current
+/// balances, before fees: what an infinitesimally small swap would get.
+pub fn spot_price(bal0: u64, bal1: u64, amp: u64) -> Option<u128> {
+    try_spot_price(bal0, bal1, amp).ok()
+}
+
+/// `spot_price` with a typed error
+pub fn try_spot_price(bal0: u64, bal1: u64, amp: u64) -> Result<u128, MathError> {
+    try_spot_price_n(&[bal0, bal1], 0, 1, amp)
+}
+
+/// Spot price of token0 in token1 as a float (1.0 at peg)
+pub fn spot_price_f64(bal0: u64, bal1: u64, amp: u64) -> Option<f64> {
+    try_spot_price_f64(bal0, bal1, amp).ok()
+}
+
+/// `spot_price_f64` with a typed error
+pub fn try_spot_price_f64(bal0: u64, bal1: u64, amp: u64) -> Result<f64, MathError> {
+    let (ann, k) = marginal_terms(&[bal0, bal1], 0, 1, amp)?;
+    let (x, y) = (bal0 as f64, bal1 as f64);
+    let (ann, k) = (ann as f64, k as f64);
+
+    Ok(y * (ann * x + k) / (x * (ann * y + k)))
+}
+
+/// Spot price of token `i` in token `j` (N-token pool), scaled by
+/// `PRICE_PRECISION`
+pub fn spot_price_n(balances: &[u64], i: usize, j: usize, amp: u64) -> Option<u128> {
+    try_spot_price_n(balances, i, j, amp).ok()
+}
+
+/// `spot_price_n` with a typed error
+pub fn try_spot_price_n(balances: &[u64], i: usize, j: usize, amp: u64) -> Result<u128, MathError> {
+    let (ann, k) = marginal_terms(balances, i, j, amp)?;
+    let (xi, xj) = (balances[i] as u128, balances[j] as u128);
+
+    // price = (ann + k / x_i) / (ann + k / x_j)
+    //       = x_j * (ann * x_i + k) / (x_i * (ann * x_j + k))
+    let di = ann
+        .checked_mul(xi)
+        .and_then(|v| v.checked_add(k))
+        .or_overflow()?;
+    let dj = ann
+        .checked_mul(xj)
+        .and_then(|v| v.checked_add(k))
+        .or_overflow()?;
+    let num = U256::from(xj)
+        .checked_mul(U256::from(di))
+        .and_then(|v| v.checked_mul(U256::from(PRICE_PRECISION)))
+        .or_overflow()?;
+    let denom = U256::from(xi).checked_mul(U256::from(dj)).or_overflow()?;
+
+    u128::try_from(num / denom).ok().or_overflow()
+}
+
+/// `(ann, k)` with `k = D^(n+1) / (n^n * prod(balances))`, so that the
+/// invariant's partial derivative in token `i` is `ann + k / x_i`
+fn marginal_terms(
+    balances: &[u64],
+    i: usize,
+    j: usize,
+    amp: u64,
+) -> Result<(u128, u128), MathError> {
+    let n = balances.len();
+    if i >= n || j >= n || i == j {
+        return Err(MathError::InvalidToken);
+    }
+
+    let d = try_calc_d_n(balances, amp)?;
+    if d == 0 {
+        return Err(MathError::ZeroBalance);
+    }
+
+    let mut k = d as u128;
+    for &b in balances {
+        k = k
+            .checked_mul(d as u128)
+            .and_then(|v| v.checked_div((b as u128).checked_mul(n as u128)?))
+            .or_overflow()?;
+    }
+
+    Ok((ann_n(amp, n)? as u128, k))
+}
+
+/// Calculate price impact for a swap, relative to the spot price.
+///
+/// Impact is `1 - execution_price / spot_price`, where the execution price
+/// `amount_out / amount_in` includes the fee.
+pub fn calc_price_impact(
+    bal_in: u64,
+    bal_out: u64,
+    amount_in: u64,
+    amp: u64,
+    fee_bps: u64,
+) -> Option<f64> {
+    try_calc_price_impact(bal_in, bal_out, amount_in, amp, fee_bps).ok()
+}
+
+/// `calc_price_impact` with a typed error
+pub fn try_calc_price_impact(
+    bal_in: u64,
+    bal_out: u64,
+    amount_in: u64,
+    amp: u64,
+    fee_bps: u64,
+) -> Result<f64, MathError> {
+    // Guard against division by zero
+    if amount_in == 0 {
+        return Err(MathError::ZeroAmount);
+    }
+
+    let amount_out = swap_out_after_fee(bal_in, bal_out, amount_in, amp, fee_bps)?;
+    let spot = try_spot_price_f64(bal_in, bal_out, amp)?;
+
+    // Price impact = 1 - (amount_out / amount_in) / spot
+    let ratio = (amount_out as f64) / (amount_in as f64);
+    Ok(1.0 - ratio / spot)
+}
+
+/// Cost breakdown of a swap between tokens of possibly different decimals.
+///
+/// Prices are in whole tokens (output per input), so they are comparable
+/// across mints. `price_impact + fee_impact` is the total shortfall of the
+/// execution price against spot.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PriceImpact {
+    /// Output in native units of the output token, after fee
+    pub amount_out: u64,
+    /// Spot price of the input token in the output token
+    pub spot_price: f64,
+    /// Realized price `amount_out / amount_in`, after fee
+    pub execution_price: f64,
+    /// Shortfall against spot caused by the curve alone
+    pub price_impact: f64,
+    /// Shortfall against spot caused by the swap fee
+    pub fee_impact: f64,
+}
+
+impl PriceImpact {
+    /// Total shortfall against spot: `1 - execution_price / spot_price`
+    pub fn total(&self) -> f64 {
+        self.price_impact + self.fee_impact
+    }
+}
+
+/// Calculate price impact for a swap between mints of different decimals.
+///
+/// Balances and `amount_in` are in native units. Both sides are scaled to
+/// the larger of the two decimals before the curve is evaluated, and the
+/// output is scaled back down (rounding down). Impact does not depend on
+/// the common scale, so if the scaled pool would overflow `u64` the scale
+/// steps down towards the smaller decimals instead of failing.
+pub fn calc_price_impact_with_decimals(
+    bal_in: u64,
+    bal_out: u64,
+    amount_in: u64,
+    decimals_in: u8,
+    decimals_out: u8,
+    amp: u64,
+    fee_bps: u64,
+) -> Option<PriceImpact> {
+    try_calc_price_impact_with_decimals(
+        bal_in,
+        bal_out,
+        amount_in,
+        decimals_in,
+        decimals_out,
+        amp,
+        fee_bps,
+    )
+    .ok()
+}
+
+/// `calc_price_impact_with_decimals` with a typed error
+pub fn try_calc_price_impact_with_decimals(
+    bal_in: u64,
+    bal_out: u64,
+    amount_in: u64,
+    decimals_in: u8,
+    decimals_out: u8,
+    amp: u64,
+    fee_bps: u64,
+) -> Result<PriceImpact, MathError> {
+    let quote = |scale: u8| {
+        let rates = [
+            decimals_rate(decimals_in, scale)?,
+            decimals_rate(decimals_out, scale)?,
+        ];
+        RatedBalances::try_new(&[bal_in, bal_out], &rates)?
+            .try_price_impact(0, 1, amount_in, amp, fee_bps)
+    };
+
+    let lo = decimals_in.min(decimals_out);
+    let hi = decimals_in.max(decimals_out);
+    let mut result = Err(MathError::Overflow);
+    for scale in (lo..=hi).rev() {
+        result = quote(scale);
+        if result != Err(MathError::Overflow) {
+            break;
+        }
+    }
+    result
+}
+
+/// Rate mapping native units of a `decimals` mint onto `scale` decimals
+fn decimals_rate(decimals: u8, scale: u8) -> Result<u128, MathError> {
+    if decimals <= scale {
+        return 10u128
+            .checked_pow((scale - decimals) as u32)
+            .and_then(|m| m.checked_mul(PRICE_PRECISION))
+            .or_overflow();
+    }
+
+    // More than 18 decimals down, every native amount rounds to zero
+    10u128
+        .checked_pow((decimals - scale) as u32)
+        .map(|m| PRICE_PRECISION / m)
+        .filter(|&rate| rate > 0)
+        .ok_or(MathError::ZeroRate)
+}
+
+/// Pool balances in native units, paired with per-token rates that map
+/// them onto the common scale the invariant is evaluated in.
+///
+/// A rate is the value of one native unit in normalized units, scaled by
+/// `PRICE_PRECISION`. For mixed-decimal mints it is a precision multiplier
+/// (`10^(max_decimals - decimals) * 1e18`); for yield-bearing tokens such as
+/// LSTs it is the oracle exchange rate. All methods take and return native
+/// amounts; normalized amounts round down, and so do native outputs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RatedBalances {
+    balances: Vec<u64>,
+    rates: Vec<u128>,
+}
+
+impl RatedBalances {
+    /// Pair `balances` with `rates` (2..=`MAX_TOKENS` tokens, non-zero rates)
+    pub fn new(balances: &[u64], rates: &[u128]) -> Option<Self> {
+        Self::try_new(balances, rates).ok()
+    }
+
+    /// `new` with a typed error
+    pub fn try_new(balances: &[u64], rates: &[u128]) -> Result<Self, MathError> {
+        let n = balances.len();
+        if !(2..=MAX_TOKENS).contains(&n) || rates.len() != n {
+            return Err(MathError::InvalidToken);
+        }
+        if rates.contains(&0) {
+            return Err(MathError::ZeroRate);
+        }
+
+        Ok(Self {
+            balances: balances.to_vec(),
+            rates: rates.to_vec(),
+        })
+    }
+
+    /// Rates that scale every mint up to the largest of `decimals`
+    pub fn from_decimals(balances: &[u64], decimals: &[u8]) -> Option<Self> {
+        Self::try_from_decimals(balances, decimals).ok()
+    }
+
+    /// `from_decimals` with a typed error
+    pub fn try_from_decimals(balances: &[u64], decimals: &[u8]) -> Result<Self, MathError> {
+        let max = *decimals.iter().max().ok_or(MathError::InvalidToken)?;
+        let rates = decimals
+            .iter()
+            .map(|&d| decimals_rate(d, max))
+            .collect::<Result<Vec<u128>, MathError>>()?;
+
+        Self::try_new(balances, &rates)
+    }
+
+    /// Pool balances without rates applied (1:1)
+    pub fn unit(balances: &[u64]) -> Option<Self> {
+        Self::try_unit(balances).ok()
+    }
+
+    /// `unit` with a typed error
+    pub fn try_unit(balances: &[u64]) -> Result<Self, MathError> {
+        Self::try_new(balances, &vec![PRICE_PRECISION; balances.len()])
+    }
+
+    /// Balances in native units
+    pub fn balances(&self) -> &[u64] {
+        &self.balances
+    }
+
+    /// Per-token rates, scaled by `PRICE_PRECISION`
+    pub fn rates(&self) -> &[u128] {
+        &self.rates
+    }
+
+    /// Convert a native amount of token `i` to normalized units
+    pub fn to_normalized(&self, i: usize, amount: u64) -> Option<u64> {
+        self.try_to_normalized(i, amount).ok()
+    }
+
+    /// `to_normalized` with a typed error
+    pub fn try_to_normalized(&self, i: usize, amount: u64) -> Result<u64, MathError> {
+        let rate = *self.rates.get(i).ok_or(MathError::InvalidToken)?;
+        let scaled = U256::from(amount)
+            .checked_mul(U256::from(rate))
+            .or_overflow()?
+            / U256::from(PRICE_PRECISION);
+        u64::try_from(scaled).ok().or_overflow()
+    }
+
+    /// Convert a normalized amount of token `i` to native units
+    pub fn to_native(&self, i: usize, amount: u64) -> Option<u64> {
+        self.try_to_native(i, amount).ok()
+    }
+
+    /// `to_native` with a typed error
+    pub fn try_to_native(&self, i: usize, amount: u64) -> Result<u64, MathError> {
+        let rate = *self.rates.get(i).ok_or(MathError::InvalidToken)?;
+        let scaled = U256::from(amount)
+            .checked_mul(U256::from(PRICE_PRECISION))
+            .or_overflow()?
+            / U256::from(rate);
+        u64::try_from(scaled).ok().or_overflow()
+    }
+
+    /// Balances in normalized units
+    pub fn normalized(&self) -> Option<Vec<u64>> {
+        self.try_normalized().ok()
+    }
+
+    /// `normalized` with a typed error
+    pub fn try_normalized(&self) -> Result<Vec<u64>, MathError> {
+        self.normalize(&self.balances)
+    }
+
+    /// Native `amounts` (one per token) in normalized units
+    fn normalize(&self, amounts: &[u64]) -> Result<Vec<u64>, MathError> {
+        amounts
+            .iter()
+            .enumerate()
+            .map(|(i, &a)| self.try_to_normalized(i, a))
+            .collect()
+    }
+
+    /// Normalized balances of a 2-token pool, ordered `(i, j)`
+    fn pair(&self, i: usize, j: usize) -> Result<(u64, u64), MathError> {
+        if self.balances.len() != 2 || i >= 2 || j >= 2 || i == j {
+            return Err(MathError::InvalidToken);
+        }
+
+        Ok((
+            self.try_to_normalized(i, self.balances[i])?,
+            self.try_to_normalized(j, self.balances[j])?,
+        ))
+    }
+
+    /// Invariant D, in normalized units
+    pub fn calc_d(&self, amp: u64) -> Option<u64> {
+        self.try_calc_d(amp).ok()
+    }
+
+    /// `calc_d` with a typed error
+    pub fn try_calc_d(&self, amp: u64) -> Result<u64, MathError> {
+        try_calc_d_n(&self.try_normalized()?, amp)
+    }
+
+    /// Simulate a swap of `amount_in` of token `i` for token `j`
+    pub fn simulate_swap(
+        &self,
+        i: usize,
+        j: usize,
+        amount_in: u64,
+        amp: u64,
+        fee_bps: u64,
+    ) -> Option<u64> {
+        self.try_simulate_swap(i, j, amount_in, amp, fee_bps).ok()
+    }
+
+    /// `simulate_swap` with a typed error
+    pub fn try_simulate_swap(
+        &self,
+        i: usize,
+        j: usize,
+        amount_in: u64,
+        amp: u64,
+        fee_bps: u64,
+    ) -> Result<u64, MathError> {
+        let dx = self.try_to_normalized(i, amount_in)?;
+        let out = try_simulate_swap_n(&self.try_normalized()?, i, j, dx, amp, fee_bps)?;
+        self.try_to_native(j, out)
+    }
+
+    /// Minimum `amount_in` of token `i` for which `simulate_swap` returns
+    /// at least `amount_out` of token `j`
+    pub fn simulate_swap_exact_out(
+        &self,
+        i: usize,
+        j: usize,
+        amount_out: u64,
+        amp: u64,
+        fee_bps: u64,
+    ) -> Option<u64> {
+        self.try_simulate_swap_exact_out(i, j, amount_out, amp, fee_bps)
+            .ok()
+    }
+
+    /// `simulate_swap_exact_out` with a typed error
+    pub fn try_simulate_swap_exact_out(
+        &self,
+        i: usize,
+        j: usize,
+        amount_out: u64,
+        amp: u64,
+        fee_bps: u64,
+    ) -> Result<u64, MathError> {
+        if amount_out == 0 {
+            return Ok(0);
+        }
+
+        let xp = self.try_normalized()?;
+        let dy = self.try_to_normalized(j, amount_out)?;
+        let estimate = try_simulate_swap_exact_out_n(&xp, i, j, dy, amp, fee_bps)?;
+
+        min_amount_in(amount_out, self.try_to_native(i, estimate)?, |amount_in| {
+            self.simulate_swap(i, j, amount_in, amp, fee_bps)
+        })
+    }
+
+    /// Simulate a migration swap of `amount_in` of token `i` for token `j`
+    /// (2-token pools only), charging `MIGRATION_FEE_BPS`
+    pub fn simulate_migration(&self, i: usize, j: usize, amount_in: u64, amp: u64) -> Option<u64> {
+        self.try_simulate_migration(i, j, amount_in, amp).ok()
+    }
+
+    /// `simulate_migration` with a typed error
+    pub fn try_simulate_migration(
+        &self,
+        i: usize,
+        j: usize,
+        amount_in: u64,
+        amp: u64,
+    ) -> Result<u64, MathError> {
+        let (x_i, x_j) = self.pair(i, j)?;
+        let dx = self.try_to_normalized(i, amount_in)?;
+        let out = migration_out(x_i, x_j, dx, amp)?;
+        self.try_to_native(j, out)
+    }
+
+    /// Spot price of token `i` in token `j` in normalized units, scaled by
+    /// `PRICE_PRECISION` (1e18 at peg, whatever the decimals)
+    pub fn spot_price(&self, i: usize, j: usize, amp: u64) -> Option<u128> {
+        self.try_spot_price(i, j, amp).ok()
+    }
+
+    /// `spot_price` with a typed error
+    pub fn try_spot_price(&self, i: usize, j: usize, amp: u64) -> Result<u128, MathError> {
+        try_spot_price_n(&self.try_normalized()?, i, j, amp)
+    }
+
+    /// Value native `amounts` (one per token) in native units of token
+    /// `numeraire`, at spot prices like `calc_value_in_token_n`
+    pub fn value_in(&self, amounts: &[u64], numeraire: usize, amp: u64) -> Option<u64> {
+        self.try_value_in(amounts, numeraire, amp).ok()
+    }
+
+    /// `value_in` with a typed error
+    pub fn try_value_in(
+        &self,
+        amounts: &[u64],
+        numeraire: usize,
+        amp: u64,
+    ) -> Result<u64, MathError> {
+        let amounts = self.normalize(amounts)?;
+        let value = try_calc_value_in_token_n(&amounts, &self.try_normalized()?, numeraire, amp)?;
+        self.try_to_native(numeraire, value)
+    }
+
+    /// Price impact of swapping `amount_in` of token `i` for token `j`
+    pub fn price_impact(
+        &self,
+        i: usize,
+        j: usize,
+        amount_in: u64,
+        amp: u64,
+        fee_bps: u64,
+    ) -> Option<PriceImpact> {
+        self.try_price_impact(i, j, amount_in, amp, fee_bps).ok()
+    }
+
+    /// `price_impact` with a typed error
+    pub fn try_price_impact(
+        &self,
+        i: usize,
+        j: usize,
+        amount_in: u64,
+        amp: u64,
+        fee_bps: u64,
+    ) -> Result<PriceImpact, MathError> {
+        let xp = self.try_normalized()?;
+        let dx = self.try_to_normalized(i, amount_in)?;
+
+        // Guard against division by zero
+        if dx == 0 {
+            return Err(MathError::ZeroAmount);
+        }
+
+        let gross = try_simulate_swap_n(&xp, i, j, dx, amp, 0)?;
+        let fee = gross.checked_mul(fee_bps).or_overflow()? / 10000;
+        let net = gross.checked_sub(fee).or_overflow()?;
+        let spot = try_spot_price_n(&xp, i, j, amp)? as f64 / PRICE_PRECISION as f64;
+
+        // Ratios of normalized amounts are whole-token prices
+        let quote = dx as f64 * spot;
+
+        Ok(PriceImpact {
+            amount_out: self.try_to_native(j, net)?,
+            spot_price: spot,
+            execution_price: net as f64 / dx as f64,
+            price_impact: 1.0 - gross as f64 / quote,
+            fee_impact: fee as f64 / quote,
+        })
+    }
+
+    /// LP tokens minted for depositing native `amounts`
+    pub fn calc_lp_tokens(&self, amounts: &[u64], lp_supply: u64, amp: u64) -> Option<u64> {
+        self.try_calc_lp_tokens(amounts, lp_supply, amp).ok()
+    }
+
+    /// `calc_lp_tokens` with a typed error
+    pub fn try_calc_lp_tokens(
+        &self,
+        amounts: &[u64],
+        lp_supply: u64,
+        amp: u64,
+    ) -> Result<u64, MathError> {
+        try_calc_lp_tokens_n(
+            &self.normalize(amounts)?,
+            &self.try_normalized()?,
+            lp_supply,
+            amp,
+        )
+    }
+
+    /// LP tokens minted for depositing native `amounts` into a 2-token
+    /// pool, charging the imbalance fee like `calc_lp_tokens_imbalanced`
+    pub fn calc_lp_tokens_imbalanced(
+        &self,
+        amounts: [u64; 2],
+        lp_supply: u64,
+        amp: u64,
+        fee_bps: u64,
+    ) -> Option<u64> {
+        self.try_calc_lp_tokens_imbalanced(amounts, lp_supply, amp, fee_bps)
+            .ok()
+    }
+
+    /// `calc_lp_tokens_imbalanced` with a typed error
+    pub fn try_calc_lp_tokens_imbalanced(
+        &self,
+        amounts: [u64; 2],
+        lp_supply: u64,
+        amp: u64,
+        fee_bps: u64,
+    ) -> Result<u64, MathError> {
+        let (x0, x1) = self.pair(0, 1)?;
+        let a0 = self.try_to_normalized(0, amounts[0])?;
+        let a1 = self.try_to_normalized(1, amounts[1])?;
+        try_calc_lp_tokens_imbalanced(a0, a1, x0, x1, lp_supply, amp, fee_bps)
+    }
+
+    /// LP tokens minted for a single-sided deposit of native `amount` of
+    /// token `index` into a 2-token pool
+    pub fn calc_lp_tokens_single(
+        &self,
+        amount: u64,
+        index: usize,
+        lp_supply: u64,
+        amp: u64,
+        fee_bps: u64,
+    ) -> Option<u64> {
+        self.try_calc_lp_tokens_single(amount, index, lp_supply, amp, fee_bps)
+            .ok()
+    }
+
+    /// `calc_lp_tokens_single` with a typed error
+    pub fn try_calc_lp_tokens_single(
+        &self,
+        amount: u64,
+        index: usize,
+        lp_supply: u64,
+        amp: u64,
+        fee_bps: u64,
+    ) -> Result<u64, MathError> {
+        let (x0, x1) = self.pair(0, 1)?;
+        let index = u8::try_from(index).map_err(|_| MathError::InvalidToken)?;
+        let amount = self.try_to_normalized(index as usize, amount)?;
+        try_calc_lp_tokens_single(amount, index, x0, x1, lp_supply, amp, fee_bps)
+    }
+
+    /// Native amounts received for burning `lp_amount` proportionally
+    pub fn calc_withdraw(&self, lp_amount: u64, lp_supply: u64) -> Option<Vec<u64>> {
+        self.try_calc_withdraw(lp_amount, lp_supply).ok()
+    }
+
+    /// `calc_withdraw` with a typed error
+    pub fn try_calc_withdraw(&self, lp_amount: u64, lp_supply: u64) -> Result<Vec<u64>, MathError> {
+        try_calc_withdraw_n(lp_amount, &self.balances, lp_supply)
+    }
+
+    /// `(amount_out, fee)` in native units of token `index` for burning
+    /// `lp_amount` into that token only
+    pub fn calc_withdraw_one(
+        &self,
+        lp_amount: u64,
+        index: usize,
+        lp_supply: u64,
+        amp: u64,
+        fee_bps: u64,
+    ) -> Option<(u64, u64)> {
+        self.try_calc_withdraw_one(lp_amount, index, lp_supply, amp, fee_bps)
+            .ok()
+    }
+
+    /// `calc_withdraw_one` with a typed error
+    pub fn try_calc_withdraw_one(
+        &self,
+        lp_amount: u64,
+        index: usize,
+        lp_supply: u64,
+        amp: u64,
+        fee_bps: u64,
+    ) -> Result<(u64, u64), MathError> {
+        let xp = self.try_normalized()?;
+        let (out, fee) = try_calc_withdraw_one(lp_amount, index, &xp, lp_supply, amp, fee_bps)?;
+        Ok((
+            self.try_to_native(index, out)?,
+            self.try_to_native(index, fee)?,
+        ))
+    }
+
+    /// Virtual price (normalized D per LP token), scaled by `PRICE_PRECISION`
+    pub fn virtual_price(&self, lp_supply: u64, amp: u64) -> Option<u128> {
+        self.try_virtual_price(lp_supply, amp).ok()
+    }
+
+    /// `virtual_price` with a typed error
+    pub fn try_virtual_price(&self, lp_supply: u64, amp: u64) -> Result<u128, MathError> {
+        if lp_supply == 0 {
+            return Err(MathError::InsufficientLiquidity);
+        }
+
+        let d = self.try_calc_d(amp)?;
+        Ok((d as u128).checked_mul(PRICE_PRECISION).or_overflow()? / lp_supply as u128)
+    }
+}
+
+/// Calculate minimum output with slippage tolerance
+pub fn calc_min_output(expected: u64, slippage_bps: u64) -> u64 {
+    let slippage = expected.saturating_mul(slippage_bps) / 10000;
+    expected.saturating_sub(slippage)
+}
+
+/// Calculate virtual price (LP value relative to underlying)
+pub fn calc_virtual_price(bal0: u64, bal1: u64, lp_supply: u64, amp: u64) -> Option<u128> {
+    try_calc_virtual_price(bal0, bal1, lp_supply, amp).ok()
+}
+
+/// `calc_virtual_price` with a typed error
+pub fn try_calc_virtual_price(
+    bal0: u64,
+    bal1: u64,
+    lp_supply: u64,
+    amp: u64,
+) -> Result<u128, MathError> {
+    if lp_supply == 0 {
+        return Err(MathError::InsufficientLiquidity);
+    }
+
+    let d = try_calc_d(bal0, bal1, amp)?;
+
+    // Virtual price = D * 1e18 / lp_supply
+    Ok((d as u128).checked_mul(PRICE_PRECISION).or_overflow()? / lp_supply as u128)
+}
+
+/// Calculate virtual price in 256-bit arithmetic, for pools whose D does
+/// not fit in a `u64`
+pub fn calc_virtual_price_wide(
+    bal0: u64,
+    bal1: u64,
+    lp_supply: u64,
+    amp: u64,
+) -> Result<u128, MathError> {
+    if lp_supply == 0 {
+        return Err(MathError::InsufficientLiquidity);
+    }
+
+    let d = calc_d_wide(bal0, bal1, amp)?;
+
+    // Virtual price = D * 1e18 / lp_supply
+    let vp = U256::from(d) * U256::from(PRICE_PRECISION) / U256::from(lp_supply);
+    u128::try_from(vp).ok().or_overflow()
+}
+
+/// Calculate invariant D for an N-token pool (2..=`MAX_TOKENS` balances).
+///
+/// For two balances this performs exactly the same integer steps as
+/// `calc_d`.
+pub fn calc_d_n(balances: &[u64], amp: u64) -> Option<u64> {
+    try_calc_d_n(balances, amp).ok()
+}
+
+/// `calc_d_n` with a typed error
+pub fn try_calc_d_n(balances: &[u64], amp: u64) -> Result<u64, MathError> {
+    let n = balances.len();
+    if !(2..=MAX_TOKENS).contains(&n) {
+        return Err(MathError::InvalidToken);
+    }
+
+    // Guard against division by zero
+    if balances.contains(&0) {
+        return Ok(0);
+    }
+
+    let s = balances
+        .iter()
+        .try_fold(0u64, |acc, &b| acc.checked_add(b))
+        .or_overflow()?;
+    let n_u = n as u128;
+    let ann = ann_n(amp, n)?;
+
+    // Guard against zero amp
+    if ann == 0 {
+        return Err(MathError::ZeroAmp);
+    }
+
+    let mut d = s;
+
+    for _ in 0..NEWTON_ITERATIONS {
+        // d_p = d^(n+1) / (n^n * prod(balances))
+        let mut d_p = d as u128;
+        for &b in balances {
+            d_p = d_p
+                .checked_mul(d as u128)
+                .and_then(|v| v.checked_div((b as u128).checked_mul(n_u)?))
+                .or_overflow()?;
+        }
+
+        let d_prev = d;
+
+        // d = (ann * s + d_p * n) * d / ((ann - 1) * d + (n + 1) * d_p)
+        let num = (ann as u128)
+            .checked_mul(s as u128)
+            .and_then(|v| v.checked_add(d_p.checked_mul(n_u)?))
+            .and_then(|v| v.checked_mul(d as u128))
+            .or_overflow()?;
+
+        let denom = ((ann - 1) as u128)
+            .checked_mul(d as u128)
+            .and_then(|v| v.checked_add(d_p.checked_mul(n_u + 1)?))
+            .or_overflow()?;
+
+        // Guard against division by zero
+        if denom == 0 {
+            return Err(MathError::NonConvergence);
+        }
+        d = u64::try_from(num / denom).ok().or_overflow()?;
+
+        // Check convergence
+        if d.abs_diff(d_prev) <= 1 {
+            return Ok(d);
+        }
+    }
+
+    Err(MathError::NonConvergence)
+}
+
+/// Calculate the new balance of token `j` after token `i` is set to
+/// `x_new`, holding D constant (N-token pool).
+///
+/// For two balances this performs exactly the same integer steps as
+/// `calc_y`.
+pub fn calc_y_n(balances: &[u64], i: usize, j: usize, x_new: u64, amp: u64) -> Option<u64> {
+    try_calc_y_n(balances, i, j, x_new, amp).ok()
+}
+
+/// `calc_y_n` with a typed error
+pub fn try_calc_y_n(
+    balances: &[u64],
+    i: usize,
+    j: usize,
+    x_new: u64,
+    amp: u64,
+) -> Result<u64, MathError> {
+    let n = balances.len();
+    if i == j || i >= n || j >= n {
+        return Err(MathError::InvalidToken);
+    }
+
+    let d = try_calc_d_n(balances, amp)?;
+    let mut xp = balances.to_vec();
+    xp[i] = x_new;
+
+    calc_y_d_n(&xp, j, d, amp)
+}
+
+/// Solve for balance `j` given the other balances in `xp` and invariant `d`
+fn calc_y_d_n(xp: &[u64], j: usize, d: u64, amp: u64) -> Result<u64, MathError> {
+    let n = xp.len();
+    if !(2..=MAX_TOKENS).contains(&n) || j >= n {
+        return Err(MathError::InvalidToken);
+    }
+
+    let n_u = n as u128;
+    let ann = ann_n(amp, n)?;
+
+    // Guard against zero amp
+    if ann == 0 {
+        return Err(MathError::ZeroAmp);
+    }
+
+    // c = d^(n+1) / (n^n * prod(x_k, k != j) * ann * n)
+    // b = sum(x_k, k != j) + d / ann
+    let mut c = d as u128;
+    let mut s = 0u128;
+    for (k, &x) in xp.iter().enumerate() {
+        if k == j {
+            continue;
+        }
+        // Guard against division by zero
+        if x == 0 {
+            return Err(MathError::ZeroBalance);
+        }
+        s = s.checked_add(x as u128).or_overflow()?;
+        c = c
+            .checked_mul(d as u128)
+            .and_then(|v| v.checked_div((x as u128).checked_mul(n_u)?))
+            .or_overflow()?;
+    }
+    c = c
+        .checked_mul(d as u128)
+        .and_then(|v| v.checked_div((ann as u128).checked_mul(n_u)?))
+        .or_overflow()?;
+    let b = s.checked_add((d / ann) as u128).or_overflow()?;
+
+    let mut y = d;
+
+    for _ in 0..NEWTON_ITERATIONS {
+        let y_prev = y;
+
+        // y = (y^2 + c) / (2y + b - d)
+        let num = (y as u128)
+            .checked_mul(y as u128)
+            .and_then(|v| v.checked_add(c))
+            .or_overflow()?;
+
+        let denom = (y as u128)
+            .checked_mul(2)
+            .and_then(|v| v.checked_add(b))
+            .and_then(|v| v.checked_sub(d as u128))
+            .or_overflow()?;
+
+        // Guard against division by zero
+        if denom == 0 {
+            return Err(MathError::NonConvergence);
+        }
+        y = u64::try_from(num / denom).ok().or_overflow()?;
+
+        // Check convergence
+        if y.abs_diff(y_prev) <= 1 {
+            return Ok(y);
+        }
+    }
+
+    Err(MathError::NonConvergence)
+}
+
+/// LP tokens for growing the pool from `old` to `new` balances:
+/// `lp_supply * (d1 - d0) / d0`.
+///
+/// Newton's D rounds up, and by more the more lopsided the pool is, so
+/// using the estimates `d0`/`d1` directly can mint LP worth more than the
+/// deposit. They only seed `d_bounds`; the quotient takes the exact D of
+/// `new` rounded down over the exact D of `old` rounded up.
+fn mint_lp(
+    lp_supply: u64,
+    old: &[u64],
+    d0: u64,
+    new: &[u64],
+    d1: u64,
+    amp: u64,
+) -> Result<u128, MathError> {
+    let (_, d0) = d_bounds(old, amp, d0)?;
+    let (d1, _) = d_bounds(new, amp, d1)?;
+    if d0 == 0 {
+        return Err(MathError::ZeroBalance);
+    }
+
+    // LP tokens = lp_supply * (d1 - d0) / d0
+    Ok((lp_supply as u128)
+        .checked_mul(d1.saturating_sub(d0))
+        .or_overflow()?
+        / d0)
+}
+
+/// Floor and ceiling of the exact invariant of `balances`, searched
+/// outward from the estimate `guess`
+fn d_bounds(balances: &[u64], amp: u64, guess: u64) -> Result<(u128, u128), MathError> {
+    use core::cmp::Ordering::{Equal, Greater};
+
+    let n = balances.len();
+    let ann = U1024::from(ann_n(amp, n)?);
+    if ann.is_zero() {
+        return Err(MathError::ZeroAmp);
+    }
+    let s = balances
+        .iter()
+        .fold(U1024::zero(), |acc, &b| acc + U1024::from(b));
+    let np = balances
+        .iter()
+        .fold(U1024::from(n).pow(U1024::from(n)), |acc, &b| {
+            acc * U1024::from(b)
+        });
+
+    // Sign of d^(n+1) + (ann - 1) * d * nP - ann * S * nP, which is
+    // increasing in d and zero at the exact invariant
+    let cmp = |d: u128| {
+        let d = U1024::from(d);
+        let lhs = d.pow(U1024::from(n + 1)) + (ann - 1) * d * np;
+        lhs.cmp(&(ann * s * np))
+    };
+
+    // Bracket the floor with cmp(lo) <= 0 < cmp(hi), then bisect
+    let (mut lo, mut hi) = (guess as u128, guess as u128);
+    let mut step = 1u128;
+    if cmp(lo) == Greater {
+        while cmp(lo) == Greater {
+            hi = lo;
+            lo = lo.saturating_sub(step);
+            step *= 2;
+        }
+    } else {
+        while cmp(hi) != Greater {
+            lo = hi;
+            hi = hi.checked_add(step).or_overflow()?;
+            step *= 2;
+        }
+    }
+    while hi - lo > 1 {
+        let mid = lo + (hi - lo) / 2;
+        if cmp(mid) == Greater {
+            hi = mid;
+        } else {
+            lo = mid;
+        }
+    }
+
+    let ceil = if cmp(lo) == Equal { lo } else { hi };
+    Ok((lo, ceil))
+}
+
+/// A * n^n
+fn ann_n(amp: u64, n: usize) -> Result<u64, MathError> {
+    (0..n)
+        .try_fold(amp, |acc, _| acc.checked_mul(n as u64))
+        .or_overflow()
+}
+
+/// Simulate a swap of token `i` for token `j` in an N-token pool
+pub fn simulate_swap_n(
+    balances: &[u64],
+    i: usize,
+    j: usize,
+    amount_in: u64,
+    amp: u64,
+    fee_bps: u64,
+) -> Option<u64> {
+    swap_out_after_fee_n(balances, i, j, amount_in, amp, fee_bps).ok()
+}
+
+/// `simulate_swap_n` with a typed error
+pub fn try_simulate_swap_n(
+    balances: &[u64],
+    i: usize,
+    j: usize,
+    amount_in: u64,
+    amp: u64,
+    fee_bps: u64,
+) -> Result<u64, MathError> {
+    swap_out_after_fee_n(balances, i, j, amount_in, amp, fee_bps)
+}
+
+fn swap_out_after_fee_n(
+    balances: &[u64],
+    i: usize,
+    j: usize,
+    amount_in: u64,
+    amp: u64,
+    fee_bps: u64,
+) -> Result<u64, MathError> {
+    let x_new = balances
+        .get(i)
+        .ok_or(MathError::InvalidToken)?
+        .checked_add(amount_in)
+        .or_overflow()?;
+    let y_new = try_calc_y_n(balances, i, j, x_new, amp)?;
+    let amount_out = balances[j]
+        .checked_sub(y_new)
+        .ok_or(MathError::InsufficientLiquidity)?;
+
+    apply_fee(amount_out, fee_bps)
+}
+
+/// Calculate LP tokens for deposit (N-token pool).
+///
+/// The first deposit into an empty pool mints the geometric mean of the
+/// amounts, so two tokens mint `sqrt(amt0 * amt1)` like `calc_lp_tokens`.
+pub fn calc_lp_tokens_n(
+    amounts: &[u64],
+    balances: &[u64],
+    lp_supply: u64,
+    amp: u64,
+) -> Option<u64> {
+    try_calc_lp_tokens_n(amounts, balances, lp_supply, amp).ok()
+}
+
+/// `calc_lp_tokens_n` with a typed error
+pub fn try_calc_lp_tokens_n(
+    amounts: &[u64],
+    balances: &[u64],
+    lp_supply: u64,
+    amp: u64,
+) -> Result<u64, MathError> {
+    if amounts.len() != balances.len() {
+        return Err(MathError::InvalidToken);
+    }
+
+    if lp_supply == 0 {
+        if !(2..=MAX_TOKENS).contains(&amounts.len()) {
+            return Err(MathError::InvalidToken);
+        }
+        return Ok(geometric_mean(amounts));
+    }
+
+    let new_balances = balances
+        .iter()
+        .zip(amounts)
+        .map(|(&b, &a)| b.checked_add(a))
+        .collect::<Option<Vec<u64>>>()
+        .or_overflow()?;
+
+    let d0 = try_calc_d_n(balances, amp)?;
+    let d1 = try_calc_d_n(&new_balances, amp)?;
+
+    if d0 == 0 {
+        return Err(MathError::ZeroBalance);
+    }
+
+    let lp = mint_lp(lp_supply, balances, d0, &new_balances, d1, amp)?;
+
+    u64::try_from(lp).ok().or_overflow()
+}
+
+/// Calculate LP tokens for deposit (N-token pool), charging the imbalance
+/// fee like `calc_lp_tokens_imbalanced`.
+///
+/// For two tokens this matches `calc_lp_tokens_imbalanced`.
+pub fn calc_lp_tokens_imbalanced_n(
+    amounts: &[u64],
+    balances: &[u64],
+    lp_supply: u64,
+    amp: u64,
+    fee_bps: u64,
+) -> Option<u64> {
+    try_calc_lp_tokens_imbalanced_n(amounts, balances, lp_supply, amp, fee_bps).ok()
+}
+
+/// `calc_lp_tokens_imbalanced_n` with a typed error
+pub fn try_calc_lp_tokens_imbalanced_n(
+    amounts: &[u64],
+    balances: &[u64],
+    lp_supply: u64,
+    amp: u64,
+    fee_bps: u64,
+) -> Result<u64, MathError> {
+    if lp_supply == 0 {
+        return try_calc_lp_tokens_n(amounts, balances, lp_supply, amp);
+    }
+    if amounts.len() != balances.len() {
+        return Err(MathError::InvalidToken);
+    }
+
+    let d0 = try_calc_d_n(balances, amp)?;
+    if d0 == 0 {
+        return Err(MathError::ZeroBalance);
+    }
+
+    let new_balances = balances
+        .iter()
+        .zip(amounts)
+        .map(|(&b, &a)| b.checked_add(a))
+        .collect::<Option<Vec<u64>>>()
+        .or_overflow()?;
+    let d1 = try_calc_d_n(&new_balances, amp)?;
+    if d1 <= d0 {
+        return Err(MathError::InsufficientLiquidity);
+    }
+
+    let n = balances.len() as u64;
+    let mut adjusted = new_balances;
+    for (adj, &old) in adjusted.iter_mut().zip(balances) {
+        let ideal = (d1 as u128).checked_mul(old as u128).or_overflow()? / d0 as u128;
+        let diff = ideal.abs_diff(*adj as u128);
+        let fee = imbalance_fee(diff, fee_bps, n)?;
+        *adj = adj.checked_sub(fee).or_overflow()?;
+    }
+
+    let d2 = try_calc_d_n(&adjusted, amp)?;
+    let lp = mint_lp(lp_supply, balances, d0, &adjusted, d2, amp)?;
+
+    u64::try_from(lp).ok().or_overflow()
+}
+
+/// Calculate tokens received for LP burn (N-token pool)
+pub fn calc_withdraw_n(lp_amount: u64, balances: &[u64], lp_supply: u64) -> Option<Vec<u64>> {
+    try_calc_withdraw_n(lp_amount, balances, lp_supply).ok()
+}
+
+/// `calc_withdraw_n` with a typed error
+pub fn try_calc_withdraw_n(
+    lp_amount: u64,
+    balances: &[u64],
+    lp_supply: u64,
+) -> Result<Vec<u64>, MathError> {
+    if lp_supply == 0 || lp_amount > lp_supply {
+        return Err(MathError::InsufficientLiquidity);
+    }
+
+    balances
+        .iter()
+        .map(|&b| {
+            let amount =
+                (b as u128).checked_mul(lp_amount as u128).or_overflow()? / lp_supply as u128;
+            Ok(amount as u64)
+        })
+        .collect()
+}
+
+/// Floor of the geometric mean of 2..=`MAX_TOKENS` amounts; for two
+/// amounts this is `isqrt(a * b)`
+fn geometric_mean(amounts: &[u64]) -> u64 {
+    // Exact: a product of up to eight u64s fits in 512 bits
+    let product = amounts
+        .iter()
+        .fold(U512::one(), |acc, &a| acc * U512::from(a));
+    let pow = |r: u64| {
+        amounts
+            .iter()
+            .fold(U512::one(), |acc, _| acc * U512::from(r))
+    };
+
+    // The mean lies between the smallest and largest amount
+    let mut lo = amounts.iter().copied().min().unwrap_or(0);
+    let mut hi = amounts.iter().copied().max().unwrap_or(0);
+    while lo < hi {
+        let mid = lo + (hi - lo).div_ceil(2);
+        if pow(mid) <= product {
+            lo = mid;
+        } else {
+            hi = mid - 1;
+        }
+    }
+
+    lo
+}
+
+/// Integer square root using Newton's method
+fn isqrt(n: u128) -> u128 {
+    if n == 0 {
+        return 0;
+    }
+    if n <= 3 {
+        return 1;
+    }
+
+    let mut x = n;
+    let mut y = x.div_ceil(2);
+
+    while y < x {
+        x = y;
+        y = (x + n / x) / 2;
+    }
+
+    x
+}
+
+#[cfg(test)]
+mod proptests;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::{MAX_AMP, MIN_AMP};
+    use crate::error::AeX402Error;
+
     #[test]
     fn test_calc_d() {
         let d = calc_d(1_000_000_000_000, 1_000_000_000_000, 1000).unwrap();
@@ -287,19 +2122,605 @@ mod tests {
     fn test_simulate_swap() {
         let bal = 1_000_000_000_000u64;
         let out = simulate_swap(bal, bal, 10_000_000_000, 1000, 30).unwrap();
-        
+
         // Output should be slightly less than input (due to fee + curve)
         assert!(out < 10_000_000_000);
         assert!(out > 9_900_000_000); // Not too much slippage
     }
 
+    #[test]
+    fn test_simulate_migration() {
+        let bal = 1_000_000_000_000u64;
+        let amount_in = 10_000_000_000;
+        let gross = simulate_swap(bal, bal, amount_in, 1000, 0).unwrap();
+        let out = simulate_migration(bal, bal, amount_in, 1000).unwrap();
+
+        assert_eq!(out, gross - gross * MIGRATION_FEE_BPS / MIGRATION_FEE_DENOM);
+        assert!(out > simulate_swap(bal, bal, amount_in, 1000, 30).unwrap());
+    }
+
+    #[test]
+    fn test_calc_lp_tokens_single() {
+        let bal = 1_000_000_000_000u64;
+        let supply = 2_000_000_000_000u64;
+        let amount = 100_000_000_000u64;
+
+        let no_fee = calc_lp_tokens(amount, 0, bal, bal, supply, 1000).unwrap();
+        let single = calc_lp_tokens_single(amount, 0, bal, bal, supply, 1000, 30).unwrap();
+        assert!(single < no_fee);
+
+        // Balanced deposits pay (almost) no imbalance fee
+        let balanced = calc_lp_tokens(amount, amount, bal, bal, supply, 1000).unwrap();
+        let balanced_fee =
+            calc_lp_tokens_imbalanced(amount, amount, bal, bal, supply, 1000, 30).unwrap();
+        assert!(balanced - balanced_fee <= 1);
+    }
+
+    #[test]
+    fn test_calc_value_in_token1() {
+        let bal = 1_000_000_000_000u64;
+        let value = calc_value_in_token1(1_000_000, 500_000, bal, bal, 1000).unwrap();
+
+        // Balanced pool prices token0 at ~1 token1
+        assert!(value <= 1_500_000);
+        assert!(value > 1_499_000);
+    }
+
+    #[test]
+    fn test_calc_value_in_token_n() {
+        let balances = [1_000_000_000_000u64, 2_000_000_000_000, 1_500_000_000_000];
+        let fees = [1_000_000u64, 500_000, 0];
+
+        // Two tokens valued in token 1 match the 2-token valuation
+        assert_eq!(
+            calc_value_in_token_n(&fees[..2], &balances[..2], 1, 1000),
+            calc_value_in_token1(fees[0], fees[1], balances[0], balances[1], 1000)
+        );
+
+        // Near peg the value is close to the plain sum in any numeraire
+        for numeraire in 0..3 {
+            let value = calc_value_in_token_n(&fees, &balances, numeraire, 1000).unwrap();
+            assert!(
+                value.abs_diff(1_500_000) < 15_000,
+                "{} {}",
+                numeraire,
+                value
+            );
+        }
+
+        assert_eq!(calc_value_in_token_n(&fees, &balances, 3, 1000), None);
+        assert_eq!(calc_value_in_token_n(&fees[..2], &balances, 0, 1000), None);
+    }
+
+    #[test]
+    fn test_spot_price() {
+        let bal = 1_000_000_000_000u64;
+        let peg = spot_price(bal, bal, 1000).unwrap();
+        assert!(peg.abs_diff(PRICE_PRECISION) < PRICE_PRECISION / 1_000_000);
+
+        // Excess token0 makes token0 cheaper, and symmetric the other way
+        let cheap = spot_price(2 * bal, bal, 100).unwrap();
+        let dear = spot_price(bal, 2 * bal, 100).unwrap();
+        assert!(cheap < PRICE_PRECISION && dear > PRICE_PRECISION);
+        let product = cheap as f64 * dear as f64 / (PRICE_PRECISION as f64).powi(2);
+        assert!((product - 1.0).abs() < 1e-9);
+
+        // Matches a tiny trade and the float variant
+        let probe = 1_000_000u64;
+        let out = calc_swap_out(2 * bal, bal, probe, 100).unwrap();
+        let probe_price = out as f64 / probe as f64;
+        let cheap_f = cheap as f64 / PRICE_PRECISION as f64;
+        assert!((probe_price - cheap_f).abs() < 1e-5);
+        assert!((spot_price_f64(2 * bal, bal, 100).unwrap() - cheap_f).abs() < 1e-12);
+
+        assert_eq!(spot_price_n(&[2 * bal, bal], 0, 1, 100), Some(cheap));
+        assert_eq!(spot_price(0, bal, 100), None);
+    }
+
+    #[test]
+    fn test_price_impact_off_peg() {
+        // A small trade in an off-peg pool has ~zero impact relative to spot
+        let bal = 1_000_000_000_000u64;
+        let impact = calc_price_impact(2 * bal, bal, 1_000_000, 100, 0).unwrap();
+        assert!(impact.abs() < 1e-5);
+
+        let large = calc_price_impact(2 * bal, bal, 100_000_000_000, 100, 0).unwrap();
+        assert!(large > impact);
+    }
+
+    #[test]
+    fn test_price_impact_with_decimals() {
+        // 6-decimal token0 vs 9-decimal token1, 1M whole tokens each side
+        let bal0 = 1_000_000_000_000u64;
+        let bal1 = 1_000_000_000_000_000u64;
+
+        // 1000 whole token0 buys ~1000 whole token1
+        let r = calc_price_impact_with_decimals(bal0, bal1, 1_000_000_000, 6, 9, 1000, 30).unwrap();
+        assert!((r.spot_price - 1.0).abs() < 1e-9);
+        assert!(r.amount_out > 996_000_000_000 && r.amount_out < 1_000_000_000_000);
+        assert!(r.price_impact >= 0.0 && r.price_impact < 1e-4);
+        assert!((r.fee_impact - 0.003).abs() < 1e-4);
+        assert!((1.0 - r.execution_price / r.spot_price - r.total()).abs() < 1e-9);
+
+        // Same decimals reduces to the plain curve
+        let same =
+            calc_price_impact_with_decimals(bal0, bal0, 1_000_000_000, 6, 6, 1000, 30).unwrap();
+        assert_eq!(
+            same.amount_out,
+            simulate_swap(bal0, bal0, 1_000_000_000, 1000, 30).unwrap()
+        );
+        let plain = calc_price_impact(bal0, bal0, 1_000_000_000, 1000, 30).unwrap();
+        assert!((same.total() - plain).abs() < 1e-9);
+
+        // Reverse direction scales the output back down to 6 decimals
+        let back =
+            calc_price_impact_with_decimals(bal1, bal0, 1_000_000_000_000, 9, 6, 1000, 30).unwrap();
+        assert!(back.amount_out > 996_000_000 && back.amount_out < 1_000_000_000);
+
+        assert!(calc_price_impact_with_decimals(bal0, bal1, 0, 6, 9, 1000, 30).is_none());
+        assert_eq!(
+            try_calc_price_impact_with_decimals(bal0, bal1, 0, 6, 9, 1000, 30),
+            Err(MathError::ZeroAmount)
+        );
+
+        // 15B whole tokens a side: 2 * 1.5e19 overflows u64 at 9 decimals,
+        // so the quote steps down to a scale that fits instead of failing
+        let big0 = 15_000_000_000_000_000u64;
+        let big1 = 15_000_000_000_000_000_000u64;
+        assert_eq!(
+            RatedBalances::try_from_decimals(&[big0, big1], &[6, 9])
+                .unwrap()
+                .try_calc_d(1000),
+            Err(MathError::Overflow)
+        );
+        let big =
+            try_calc_price_impact_with_decimals(big0, big1, 1_000_000_000_000, 6, 9, 1000, 30)
+                .unwrap();
+        assert!((big.spot_price - 1.0).abs() < 1e-9);
+        assert!(big.amount_out > 996_000_000_000_000 && big.amount_out < 1_000_000_000_000_000);
+        assert!((big.fee_impact - 0.003).abs() < 1e-4);
+        let big_back =
+            try_calc_price_impact_with_decimals(big1, big0, 1_000_000_000_000_000, 9, 6, 1000, 30)
+                .unwrap();
+        assert!(big_back.amount_out > 996_000_000_000 && big_back.amount_out < 1_000_000_000_000);
+
+        // Mints 19+ decimals apart cannot share a scale
+        assert_eq!(
+            try_calc_price_impact_with_decimals(u64::MAX, u64::MAX, 1_000_000, 0, 30, 1000, 30),
+            Err(MathError::ZeroRate)
+        );
+    }
+
+    #[test]
+    fn test_rated_balances() {
+        // 6-decimal token0 vs 9-decimal token1, 1M whole tokens each side
+        let rated =
+            RatedBalances::from_decimals(&[1_000_000_000_000, 1_000_000_000_000_000], &[6, 9])
+                .unwrap();
+        assert_eq!(rated.rates(), &[1000 * PRICE_PRECISION, PRICE_PRECISION]);
+        assert_eq!(rated.normalized().unwrap(), vec![1_000_000_000_000_000; 2]);
+
+        let out = rated.simulate_swap(0, 1, 1_000_000, 1000, 30).unwrap();
+        assert!(out > 996_000_000 && out < 1_000_000_000);
+        let back = rated.simulate_swap(1, 0, 1_000_000_000, 1000, 30).unwrap();
+        assert!(back > 996_000 && back < 1_000_000);
+
+        let amount_in = rated
+            .simulate_swap_exact_out(0, 1, 500_000_000, 1000, 30)
+            .unwrap();
+        assert!(rated.simulate_swap(0, 1, amount_in, 1000, 30).unwrap() >= 500_000_000);
+        assert!(rated.simulate_swap(0, 1, amount_in - 1, 1000, 30).unwrap() < 500_000_000);
+
+        let peg = rated.spot_price(0, 1, 1000).unwrap();
+        assert!(peg.abs_diff(PRICE_PRECISION) < PRICE_PRECISION / 1_000_000);
+
+        // Balanced deposit in native units mints a proportional share
+        let supply = 2_000_000_000_000_000u64;
+        let lp = rated
+            .calc_lp_tokens(&[10_000_000_000, 10_000_000_000_000], supply, 1000)
+            .unwrap();
+        assert!(supply / 100 - lp <= 2);
+        let (one, fee) = rated.calc_withdraw_one(lp, 0, supply, 1000, 30).unwrap();
+        assert!(one > 19_900_000_000 && one < 20_000_000_000 && fee > 0);
+
+        let vp = rated.virtual_price(supply, 1000).unwrap();
+        assert!(vp.abs_diff(PRICE_PRECISION) < PRICE_PRECISION / 1_000_000);
+
+        // Unit rates match the raw functions
+        let bal = [1_000_000_000_000u64, 800_000_000_000];
+        let unit = RatedBalances::unit(&bal).unwrap();
+        assert_eq!(
+            unit.simulate_swap(0, 1, 1_000_000, 100, 30),
+            simulate_swap(bal[0], bal[1], 1_000_000, 100, 30)
+        );
+        assert_eq!(
+            unit.simulate_migration(0, 1, 1_000_000, 100),
+            simulate_migration(bal[0], bal[1], 1_000_000, 100)
+        );
+        assert_eq!(
+            unit.value_in(&[1_000_000, 5], 1, 100),
+            calc_value_in_token1(1_000_000, 5, bal[0], bal[1], 100)
+        );
+        assert_eq!(
+            unit.calc_lp_tokens_imbalanced([1_000_000, 0], 2_000_000_000_000, 100, 30),
+            calc_lp_tokens_imbalanced(1_000_000, 0, bal[0], bal[1], 2_000_000_000_000, 100, 30)
+        );
+        assert_eq!(
+            unit.calc_lp_tokens_single(1_000_000, 1, 2_000_000_000_000, 100, 30),
+            calc_lp_tokens_single(1_000_000, 1, bal[0], bal[1], 2_000_000_000_000, 100, 30)
+        );
+
+        // Rated quotes in native units of the 9-decimal token
+        let mig = rated.simulate_migration(0, 1, 1_000_000, 1000).unwrap();
+        assert!(mig > out && mig < 1_000_000_000);
+        let value = rated
+            .value_in(&[1_000_000, 1_000_000_000], 1, 1000)
+            .unwrap();
+        assert!(value.abs_diff(2_000_000_000) < 2_000);
+
+        // An LST worth 1.1 of the other token trades near that rate
+        let lst = RatedBalances::new(&bal, &[1_100_000_000_000_000_000, PRICE_PRECISION]).unwrap();
+        let lst_out = lst.simulate_swap(0, 1, 1_000_000, 1000, 0).unwrap();
+        assert!(lst_out > 1_090_000 && lst_out < 1_100_000);
+
+        assert!(RatedBalances::new(&bal, &[PRICE_PRECISION, 0]).is_none());
+        assert!(RatedBalances::new(&bal, &[PRICE_PRECISION]).is_none());
+
+        // Result forms say why
+        assert_eq!(
+            RatedBalances::try_new(&bal, &[PRICE_PRECISION, 0]),
+            Err(MathError::ZeroRate)
+        );
+        assert_eq!(
+            RatedBalances::try_new(&bal, &[PRICE_PRECISION]),
+            Err(MathError::InvalidToken)
+        );
+        assert_eq!(
+            RatedBalances::try_from_decimals(&bal, &[]),
+            Err(MathError::InvalidToken)
+        );
+        assert_eq!(
+            rated.try_price_impact(0, 1, 0, 1000, 30),
+            Err(MathError::ZeroAmount)
+        );
+        assert_eq!(
+            rated.try_simulate_swap(0, 2, 1_000_000, 1000, 30),
+            Err(MathError::InvalidToken)
+        );
+        assert_eq!(
+            rated.try_virtual_price(0, 1000),
+            Err(MathError::InsufficientLiquidity)
+        );
+        assert_eq!(
+            rated.try_to_normalized(0, u64::MAX),
+            Err(MathError::Overflow)
+        );
+        let three = RatedBalances::unit(&[bal[0], bal[1], bal[1]]).unwrap();
+        assert_eq!(
+            three.try_simulate_migration(0, 1, 1_000_000, 100),
+            Err(MathError::InvalidToken)
+        );
+        assert_eq!(
+            rated.try_calc_withdraw_one(lp, 0, supply, 1000, 30).ok(),
+            rated.calc_withdraw_one(lp, 0, supply, 1000, 30)
+        );
+    }
+
+    #[test]
+    fn test_get_current_amp() {
+        assert_eq!(get_current_amp(1000, 2000, 100, 300, 200), 1500);
+        assert_eq!(get_current_amp(2000, 1000, 100, 300, 200), 1500);
+        assert_eq!(get_current_amp(1000, 2000, 100, 300, 100), 1000);
+        assert_eq!(get_current_amp(1000, 2000, 100, 300, 300), 2000);
+        assert_eq!(get_current_amp(1000, 2000, 100, 100, 50), 2000);
+
+        // Full MIN_AMP..MAX_AMP swing over the longest ramps i64 allows
+        let mid = MIN_AMP + (MAX_AMP - MIN_AMP) / 2;
+        assert_eq!(
+            try_get_current_amp(MIN_AMP, MAX_AMP, i64::MIN, i64::MAX, 0),
+            Ok(mid)
+        );
+        assert_eq!(
+            try_get_current_amp(MAX_AMP, MIN_AMP, 0, i64::MAX, i64::MAX - 1),
+            Ok(MIN_AMP + 1)
+        );
+
+        // A century-long ramp stays monotonic and within its endpoints
+        let century = 100 * 365 * 86_400i64;
+        let mut prev = MIN_AMP;
+        for step in 0..=100 {
+            let amp =
+                try_get_current_amp(MIN_AMP, MAX_AMP, 0, century, century / 100 * step).unwrap();
+            assert!(amp >= prev && amp <= MAX_AMP);
+            prev = amp;
+        }
+        assert_eq!(
+            try_get_current_amp(0, u64::MAX, 0, century, century / 2),
+            Ok(u64::MAX / 2)
+        );
+
+        // Inverted ramps are rejected; the infallible form clamps to an endpoint
+        assert_eq!(
+            try_get_current_amp(1000, 2000, 300, 100, 200),
+            Err(MathError::InvalidRamp)
+        );
+        assert_eq!(get_current_amp(1000, 2000, 300, 100, 50), 1000);
+        assert_eq!(get_current_amp(1000, 2000, 300, 100, 200), 2000);
+        assert_eq!(
+            AeX402Error::from(MathError::InvalidRamp),
+            AeX402Error::RampConstraint
+        );
+    }
+
+    #[test]
+    fn test_typed_errors() {
+        let bal = 1_000_000_000_000u64;
+
+        // Tripling D at the largest supply mints more than a u64 holds
+        assert_eq!(
+            try_calc_lp_tokens(2 * bal, 2 * bal, bal, bal, u64::MAX, 100),
+            Err(MathError::Overflow)
+        );
+        assert_eq!(
+            calc_lp_tokens(2 * bal, 2 * bal, bal, bal, u64::MAX, 100),
+            None
+        );
+
+        assert_eq!(
+            try_simulate_swap(bal, bal, 1_000_000, 0, 30),
+            Err(MathError::ZeroAmp)
+        );
+        assert_eq!(
+            try_simulate_swap(0, bal, 1_000_000, 100, 30),
+            Err(MathError::ZeroBalance)
+        );
+        assert_eq!(
+            try_calc_price_impact(bal, bal, 0, 100, 30),
+            Err(MathError::ZeroAmount)
+        );
+        assert_eq!(
+            try_simulate_swap(u64::MAX, bal, 1_000_000, 100, 30),
+            Err(MathError::Overflow)
+        );
+        assert_eq!(
+            try_simulate_swap_n(&[bal, bal], 0, 2, 1_000_000, 100, 30),
+            Err(MathError::InvalidToken)
+        );
+        assert_eq!(
+            try_simulate_swap_exact_out(bal, bal, bal, 100, 30),
+            Err(MathError::InsufficientLiquidity)
+        );
+        assert_eq!(
+            try_calc_withdraw(1, bal, bal, 0),
+            Err(MathError::InsufficientLiquidity)
+        );
+        assert_eq!(try_calc_d_n(&[bal], 100), Err(MathError::InvalidToken));
+
+        // Option variants agree with the Result variants
+        assert_eq!(
+            try_simulate_swap(bal, bal, 10_000_000_000, 1000, 30).ok(),
+            simulate_swap(bal, bal, 10_000_000_000, 1000, 30)
+        );
+        assert_eq!(
+            try_calc_d(bal, 2 * bal, 100).ok(),
+            calc_d(bal, 2 * bal, 100)
+        );
+
+        // MIN_SWAP is checked separately, so both forms quote dust alike
+        assert_eq!(check_min_swap(MIN_SWAP - 1), Err(MathError::BelowMinSwap));
+        assert_eq!(check_min_swap(MIN_SWAP), Ok(()));
+        assert_eq!(
+            check_min_deposit(MIN_DEPOSIT - 1),
+            Err(MathError::BelowMinDeposit)
+        );
+        assert_eq!(check_min_deposit(0), Ok(()));
+        assert_eq!(
+            try_simulate_swap(bal, bal, MIN_SWAP - 1, 100, 30).ok(),
+            simulate_swap(bal, bal, MIN_SWAP - 1, 100, 30)
+        );
+        assert!(simulate_swap(bal, bal, MIN_SWAP - 1, 100, 30).is_some());
+
+        // Mapped to program errors
+        assert_eq!(
+            AeX402Error::from(MathError::ZeroAmp),
+            AeX402Error::InvalidAmp
+        );
+        assert_eq!(
+            AeX402Error::from(MathError::Overflow),
+            AeX402Error::MathOverflow
+        );
+        assert_eq!(
+            AeX402Error::from(MathError::ZeroAmount),
+            AeX402Error::ZeroAmount
+        );
+    }
+
+    #[test]
+    fn test_wide_large_balances() {
+        // D exceeds u64::MAX: the u128 path refuses instead of truncating
+        let bal = u64::MAX / 2 + 1;
+        assert_eq!(try_calc_d(bal, bal, 1000), Err(MathError::Overflow));
+
+        let d = calc_d_wide(bal, bal, 1000).unwrap();
+        assert!(d >= 2 * bal as u128 && d - 2 * bal as u128 <= 2);
+
+        let y = calc_y_wide(bal, d, 1000).unwrap();
+        assert!(y.abs_diff(bal) <= 2);
+
+        let vp = calc_virtual_price_wide(bal, bal, bal, 1000).unwrap();
+        assert!(vp.abs_diff(2 * PRICE_PRECISION) < PRICE_PRECISION / 1_000_000);
+
+        assert_eq!(calc_d_wide(bal, bal, 0), Err(MathError::ZeroAmp));
+        assert_eq!(
+            calc_y_wide(u64::MAX, u128::MAX, 1),
+            Err(MathError::Overflow)
+        );
+    }
+
+    #[test]
+    fn test_n_token_matches_two_token() {
+        let balances = [
+            (1_000_000_000_000u64, 1_000_000_000_000u64),
+            (1_000_000_000_000, 3_000_000_000),
+            (123_456_789, 987_654_321_000),
+            (5_000_000, 5_000_001),
+        ];
+
+        for amp in [1, 10, 100, 1000, 100_000] {
+            for &(x, y) in &balances {
+                assert_eq!(calc_d_n(&[x, y], amp), calc_d(x, y, amp));
+
+                let d = calc_d(x, y, amp).unwrap();
+                let x_new = x + x / 10;
+                assert_eq!(calc_y_n(&[x, y], 0, 1, x_new, amp), calc_y(x_new, d, amp));
+
+                assert_eq!(
+                    simulate_swap_n(&[x, y], 0, 1, x / 100, amp, 30),
+                    simulate_swap(x, y, x / 100, amp, 30)
+                );
+                assert_eq!(
+                    calc_lp_tokens_imbalanced_n(&[x / 10, 0], &[x, y], x + y, amp, 30),
+                    calc_lp_tokens_imbalanced(x / 10, 0, x, y, x + y, amp, 30)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_simulate_swap_n() {
+        let bal = 1_000_000_000_000u64;
+        let balances = [bal, bal, bal];
+        let out = simulate_swap_n(&balances, 0, 2, 10_000_000_000, 1000, 30).unwrap();
+
+        assert!(out < 10_000_000_000);
+        assert!(out > 9_900_000_000);
+        assert!(simulate_swap_n(&balances, 1, 1, 1_000_000, 1000, 30).is_none());
+        assert!(simulate_swap_n(&balances, 0, 3, 1_000_000, 1000, 30).is_none());
+    }
+
+    #[test]
+    fn test_calc_lp_tokens_n() {
+        let bal = 1_000_000_000_000u64;
+        let balances = [bal, bal, bal, bal];
+        let supply = 4_000_000_000_000u64;
+
+        // Proportional deposit mints proportional LP
+        let amounts = [bal / 100; 4];
+        let lp = calc_lp_tokens_n(&amounts, &balances, supply, 1000).unwrap();
+        assert!(supply / 100 - lp <= 4);
+
+        let out = calc_withdraw_n(lp, &balances, supply).unwrap();
+        assert!(out.iter().all(|&o| o <= bal / 100));
+
+        // The first deposit agrees with the 2-token sqrt(amt0 * amt1)
+        let firsts = [
+            (1_000_000u64, 1_000_000u64),
+            (1_000_000_000_000, 3),
+            (u64::MAX, u64::MAX - 1),
+            (7, 0),
+        ];
+        for &(a, b) in &firsts {
+            assert_eq!(
+                calc_lp_tokens_n(&[a, b], &[0, 0], 0, 100),
+                calc_lp_tokens(a, b, 0, 0, 0, 100)
+            );
+        }
+
+        // ...and mints the geometric mean for more tokens
+        assert_eq!(calc_lp_tokens_n(&[bal; 8], &[0; 8], 0, 100), Some(bal));
+        assert_eq!(
+            calc_lp_tokens_n(&[1000, 8000, 27_000], &[0; 3], 0, 100),
+            Some(6000)
+        );
+        assert_eq!(
+            calc_lp_tokens_n(&[u64::MAX; 8], &[0; 8], 0, 100),
+            Some(u64::MAX)
+        );
+        assert_eq!(calc_lp_tokens_n(&[bal], &[0], 0, 100), None);
+
+        // Lopsided deposits pay the imbalance fee; balanced ones barely do
+        let lopsided = [bal / 25, 0, 0, 0];
+        let fee_free = calc_lp_tokens_n(&lopsided, &balances, supply, 1000).unwrap();
+        let charged = calc_lp_tokens_imbalanced_n(&lopsided, &balances, supply, 1000, 30).unwrap();
+        assert!(charged < fee_free);
+        let balanced = calc_lp_tokens_imbalanced_n(&amounts, &balances, supply, 1000, 30).unwrap();
+        assert!(lp - balanced <= 4);
+        assert_eq!(
+            try_calc_lp_tokens_imbalanced_n(&[1; 3], &balances, supply, 1000, 30),
+            Err(MathError::InvalidToken)
+        );
+    }
+
+    #[test]
+    fn test_simulate_swap_exact_out() {
+        let cases = [
+            (1_000_000_000_000u64, 1_000_000_000_000u64, 1000u64),
+            (1_000_000_000_000, 50_000_000_000, 100),
+            (20_000_000_000, 900_000_000_000, 10),
+        ];
+
+        for &(bal_in, bal_out, amp) in &cases {
+            for target in [100_000u64, 12_345_678, 1_000_000_000, 10_000_000_000] {
+                let amount_in = simulate_swap_exact_out(bal_in, bal_out, target, amp, 30).unwrap();
+                let out = simulate_swap(bal_in, bal_out, amount_in, amp, 30).unwrap();
+                assert!(out >= target);
+
+                // Minimal: one less under-delivers
+                let less = simulate_swap(bal_in, bal_out, amount_in - 1, amp, 30).unwrap();
+                assert!(less < target);
+            }
+        }
+
+        // Cannot take the whole output balance
+        assert!(simulate_swap_exact_out(1_000_000, 1_000_000, 1_000_000, 100, 30).is_none());
+    }
+
+    #[test]
+    fn test_simulate_swap_exact_out_n() {
+        let balances = [1_000_000_000_000u64, 800_000_000_000, 1_200_000_000_000];
+
+        for target in [100_000u64, 7_654_321, 5_000_000_000] {
+            let amount_in = simulate_swap_exact_out_n(&balances, 2, 1, target, 500, 4).unwrap();
+            let out = simulate_swap_n(&balances, 2, 1, amount_in, 500, 4).unwrap();
+            assert!(out >= target);
+            assert!(simulate_swap_n(&balances, 2, 1, amount_in - 1, 500, 4).unwrap() < target);
+        }
+    }
+
+    #[test]
+    fn test_calc_withdraw_one() {
+        let bal = 1_000_000_000_000u64;
+        let supply = 2_000_000_000_000u64;
+        let lp = 20_000_000_000u64;
+
+        // Balanced pool: one-sided exit is worth about the proportional share
+        let (out, fee) = calc_withdraw_one(lp, 1, &[bal, bal], supply, 1000, 30).unwrap();
+        assert!(out < lp);
+        assert!(out > 19_900_000_000);
+        assert!(fee > 0);
+
+        // Exiting into the scarce token costs more
+        let skewed = [1_900_000_000_000u64, 100_000_000_000];
+        let (scarce, scarce_fee) = calc_withdraw_one(lp, 1, &skewed, supply, 100, 30).unwrap();
+        let (plenty, plenty_fee) = calc_withdraw_one(lp, 0, &skewed, supply, 100, 30).unwrap();
+        assert!(scarce < plenty);
+        assert!(scarce_fee > 0 && plenty_fee > 0);
+
+        // Three-token pool
+        let (out, _) = calc_withdraw_one(lp, 2, &[bal, bal, bal], 3 * bal, 1000, 30).unwrap();
+        assert!(out > 19_900_000_000 && out < lp);
+
+        assert!(calc_withdraw_one(supply, 0, &[bal, bal], supply, 1000, 30).is_none());
+        assert!(calc_withdraw_one(lp, 2, &[bal, bal], supply, 1000, 30).is_none());
+    }
+
     #[test]
     fn test_price_impact() {
         let bal = 1_000_000_000_000u64;
         let impact = calc_price_impact(bal, bal, 10_000_000_000, 1000, 30).unwrap();
-        
+
         // Price impact should be small for balanced pool
         assert!(impact < 0.01); // Less than 1%
-        assert!(impact > 0.0);  // But non-zero
+        assert!(impact > 0.0); // But non-zero
     }
 }
@@ -0,0 +1,332 @@
+//! Property-based and differential tests for the StableSwap math.
+//!
+//! `reference` solves the invariant exactly with big integers: instead of
+//! Newton's method it bisects on the sign of the invariant polynomial, so
+//! it shares none of the rounding in the production code.
+
+use super::*;
+use crate::constants::{MAX_AMP, MIN_AMP};
+use proptest::prelude::*;
+
+mod reference {
+    use num_bigint::BigInt;
+
+    fn big(v: u64) -> BigInt {
+        BigInt::from(v)
+    }
+
+    /// `ann = A * n^n`, `n^n * prod(balances)`, `sum(balances)`
+    fn terms(balances: &[u64], amp: u64) -> (BigInt, BigInt, BigInt) {
+        let n = balances.len() as u32;
+        let nn = BigInt::from(n).pow(n);
+        let prod = balances.iter().fold(nn.clone(), |acc, &b| acc * big(b));
+        let sum = balances.iter().map(|&b| big(b)).sum();
+        (big(amp) * nn, prod, sum)
+    }
+
+    /// Invariant at `d` scaled by `n^n * prod(balances)`:
+    /// `(ann - 1) * d * nP + d^(n+1) - ann * S * nP`, increasing in `d`
+    fn g(balances: &[u64], amp: u64, d: &BigInt) -> BigInt {
+        let n = balances.len() as u32;
+        let (ann, np, s) = terms(balances, amp);
+        (&ann - 1) * d * &np + d.pow(n + 1) - ann * s * np
+    }
+
+    /// Whether the exact invariant of `balances` is at least `d`
+    fn d_at_least(balances: &[u64], amp: u64, d: u128) -> bool {
+        g(balances, amp, &BigInt::from(d)) <= BigInt::from(0)
+    }
+
+    /// Floor of the exact invariant D (it lies in `(0, sum]`)
+    pub fn calc_d(balances: &[u64], amp: u64) -> u128 {
+        let (mut lo, mut hi) = (0u128, balances.iter().map(|&b| b as u128).sum::<u128>());
+        while lo < hi {
+            let mid = lo + (hi - lo).div_ceil(2);
+            if d_at_least(balances, amp, mid) {
+                lo = mid;
+            } else {
+                hi = mid - 1;
+            }
+        }
+        lo
+    }
+
+    /// Smallest balance `y` of token `j` keeping the invariant at `d`,
+    /// given the other balances in `xp` (`xp[j]` is ignored)
+    pub fn calc_y(xp: &[u64], j: usize, d: u64, amp: u64) -> u128 {
+        let n = xp.len() as u32;
+        let others: Vec<u64> = xp
+            .iter()
+            .enumerate()
+            .filter(|&(k, _)| k != j)
+            .map(|(_, &x)| x)
+            .collect();
+        let nn = BigInt::from(n).pow(n);
+        let ann = big(amp) * &nn;
+        let np = others.iter().fold(nn, |acc, &x| acc * big(x));
+        let s: BigInt = others.iter().map(|&x| big(x)).sum();
+        let d = big(d);
+
+        // n^n * P' * y * (ann * (S' + y) + d - ann * d) - d^(n+1), increasing in y
+        let h = |y: u128| {
+            let y = BigInt::from(y);
+            &np * &y * (&ann * (&s + &y) + &d - &ann * &d) - d.pow(n + 1)
+        };
+
+        let mut hi = 1u128;
+        while h(hi) < BigInt::from(0) {
+            hi *= 2;
+        }
+        let mut lo = 0u128;
+        while hi - lo > 1 {
+            let mid = lo + (hi - lo) / 2;
+            if h(mid) >= BigInt::from(0) {
+                hi = mid;
+            } else {
+                lo = mid;
+            }
+        }
+        hi
+    }
+}
+
+/// Balances from 1e3 up to ~1e16, so pairs span ratios up to ~1e13
+fn balance() -> impl Strategy<Value = u64> {
+    (1u64..=99_999, 3u32..=12).prop_map(|(m, e)| m * 10u64.pow(e) / 100)
+}
+
+fn amp() -> impl Strategy<Value = u64> {
+    prop_oneof![MIN_AMP..=100, 100..=MAX_AMP, Just(MIN_AMP), Just(MAX_AMP)]
+}
+
+/// Relative tolerance for Newton results against the exact reference
+fn close(actual: u128, exact: u128) -> bool {
+    actual.abs_diff(exact) <= 2 + exact / 1_000_000_000_000
+}
+
+/// How far Newton's D may overshoot the exact invariant: the truncating
+/// divisions in `d_p` only ever round D up, by roughly the fourth root of
+/// the balance ratio per token (2 at peg, ~100 at 1e8:1 for two tokens)
+fn d_overshoot(balances: &[u64]) -> u128 {
+    let max = *balances.iter().max().unwrap() as u128;
+    let min = *balances.iter().min().unwrap() as u128;
+    balances.len() as u128 * (2 + isqrt(isqrt(max / min)))
+}
+
+proptest! {
+    #[test]
+    fn prop_calc_d_matches_reference(x in balance(), y in balance(), amp in amp()) {
+        let Ok(d) = try_calc_d(x, y, amp) else { return Ok(()) };
+        let exact = reference::calc_d(&[x, y], amp);
+        let over = (d as u128).checked_sub(exact);
+        let tolerance = d_overshoot(&[x, y]);
+        prop_assert!(over.is_some_and(|o| o <= tolerance), "calc_d {} vs exact {}", d, exact);
+    }
+
+    #[test]
+    fn prop_calc_d_n_matches_reference(
+        balances in prop::collection::vec(balance(), 2..=MAX_TOKENS),
+        amp in amp(),
+    ) {
+        let Ok(d) = try_calc_d_n(&balances, amp) else { return Ok(()) };
+        let exact = reference::calc_d(&balances, amp);
+        // Truncation compounds over more tokens: allow another 1 ppm
+        let tolerance = d_overshoot(&balances) + exact / 1_000_000;
+        let over = (d as u128).checked_sub(exact);
+        prop_assert!(over.is_some_and(|o| o <= tolerance), "calc_d_n {} vs exact {}", d, exact);
+    }
+
+    #[test]
+    fn prop_calc_y_matches_reference(
+        x in balance(),
+        y in balance(),
+        dx in balance(),
+        amp in amp(),
+    ) {
+        let Ok(d) = try_calc_d(x, y, amp) else { return Ok(()) };
+        let Some(x_new) = x.checked_add(dx) else { return Ok(()) };
+        let Ok(new_y) = try_calc_y(x_new, d, amp) else { return Ok(()) };
+        let exact = reference::calc_y(&[x_new, 0], 1, d, amp);
+        prop_assert!(close(new_y as u128, exact), "calc_y {} vs exact {}", new_y, exact);
+    }
+
+    #[test]
+    fn prop_swap_bounded_by_invariant(
+        bal_in in balance(),
+        bal_out in balance(),
+        amount_in in balance(),
+        amp in amp(),
+    ) {
+        let Ok(out) = try_simulate_swap(bal_in, bal_out, amount_in, amp, 0) else {
+            return Ok(());
+        };
+
+        // Exact curve output holding the (floored) exact D; Newton rounding
+        // may pay out at most two units more, before any fee
+        let d = reference::calc_d(&[bal_in, bal_out], amp) as u64;
+        let min_y = reference::calc_y(&[bal_in + amount_in, 0], 1, d, amp);
+        let max_out = (bal_out as u128).saturating_sub(min_y);
+        prop_assert!(out as u128 <= max_out + 2, "out {} vs exact {}", out, max_out);
+    }
+
+    #[test]
+    fn prop_swap_monotonic_in_amount(
+        bal_in in balance(),
+        bal_out in balance(),
+        amount_in in balance(),
+        extra in balance(),
+        amp in amp(),
+    ) {
+        let Ok(out) = try_simulate_swap(bal_in, bal_out, amount_in, amp, 30) else {
+            return Ok(());
+        };
+        let Some(larger) = amount_in.checked_add(extra) else { return Ok(()) };
+        let Ok(more) = try_simulate_swap(bal_in, bal_out, larger, amp, 30) else {
+            return Ok(());
+        };
+        prop_assert!(more >= out, "{} in -> {}, {} in -> {}", amount_in, out, larger, more);
+    }
+
+    #[test]
+    fn prop_swap_monotonic_in_amp(
+        bal_out in balance(),
+        excess in 0u64..=1_000_000,
+        amount_in in balance(),
+        amp in MIN_AMP..MAX_AMP,
+        step in 1u64..=1000,
+    ) {
+        // Selling the token the pool holds more of: a flatter curve (higher
+        // amp) keeps its price closer to peg, so output cannot fall
+        let bal_in = bal_out.saturating_add(bal_out / 1_000_000 * excess);
+        let higher = (amp + step).min(MAX_AMP);
+        let Ok(out) = try_simulate_swap(bal_in, bal_out, amount_in, amp, 0) else {
+            return Ok(());
+        };
+        let Ok(flatter) = try_simulate_swap(bal_in, bal_out, amount_in, higher, 0) else {
+            return Ok(());
+        };
+        prop_assert!(flatter + 2 >= out, "amp {} -> {}, amp {} -> {}", amp, out, higher, flatter);
+    }
+
+    #[test]
+    fn prop_proportional_round_trip_never_profits(
+        bal in balance(),
+        ratio in 1u64..=1000,
+        share_bps in 1u64..=10_000,
+        amp in amp(),
+    ) {
+        // Whole multiples of 10_000, so both amounts are exactly `share_bps`
+        // of their balance: anything less even is an imbalanced deposit
+        let bal0 = bal - bal % 10_000;
+        let bal1 = bal.saturating_mul(ratio) / 10;
+        let bal1 = bal1 - bal1 % 10_000;
+        prop_assume!(bal0 > 0 && bal1 > 0);
+        let supply = bal0.max(bal1);
+        let (amt0, amt1) = (bal0 / 10_000 * share_bps, bal1 / 10_000 * share_bps);
+
+        let Ok(lp) = try_calc_lp_tokens(amt0, amt1, bal0, bal1, supply, amp) else {
+            return Ok(());
+        };
+        let (new0, new1) = (bal0 + amt0, bal1 + amt1);
+        let Ok((out0, out1)) = try_calc_withdraw(lp, new0, new1, supply + lp) else {
+            return Ok(());
+        };
+        prop_assert!(out0 <= amt0 && out1 <= amt1, "in {}/{} out {}/{}", amt0, amt1, out0, out1);
+    }
+
+    #[test]
+    fn prop_round_trip_never_dilutes_lps(
+        bal0 in balance(),
+        bal1 in balance(),
+        amt0 in balance(),
+        amt1 in balance(),
+        amp in amp(),
+    ) {
+        let supply = bal0.max(bal1);
+        let Ok(lp) = try_calc_lp_tokens(amt0, amt1, bal0, bal1, supply, amp) else {
+            return Ok(());
+        };
+        let (new0, new1) = (bal0 + amt0, bal1 + amt1);
+        let Ok((out0, out1)) = try_calc_withdraw(lp, new0, new1, supply + lp) else {
+            return Ok(());
+        };
+
+        // Remaining LPs hold at least the exact D per share they had before
+        let d0 = reference::calc_d(&[bal0, bal1], amp);
+        let d2 = reference::calc_d(&[new0 - out0, new1 - out1], amp);
+        prop_assert!(d2 >= d0, "D {} -> {}", d0, d2);
+    }
+
+    #[test]
+    fn prop_imbalanced_round_trip_never_dilutes_lps(
+        bal0 in balance(),
+        bal1 in balance(),
+        amt0 in prop_oneof![Just(0u64), balance()],
+        amt1 in prop_oneof![Just(0u64), balance()],
+        amp in amp(),
+        fee_bps in 0u64..=100,
+    ) {
+        let supply = bal0.max(bal1);
+        let Ok(lp) =
+            try_calc_lp_tokens_imbalanced(amt0, amt1, bal0, bal1, supply, amp, fee_bps)
+        else {
+            return Ok(());
+        };
+        let Ok(balanced) = try_calc_lp_tokens(amt0, amt1, bal0, bal1, supply, amp) else {
+            return Ok(());
+        };
+        prop_assert!(lp <= balanced, "imbalanced {} vs balanced {}", lp, balanced);
+
+        let (new0, new1) = (bal0 + amt0, bal1 + amt1);
+        let Ok((out0, out1)) = try_calc_withdraw(lp, new0, new1, supply + lp) else {
+            return Ok(());
+        };
+        let d0 = reference::calc_d(&[bal0, bal1], amp);
+        let d2 = reference::calc_d(&[new0 - out0, new1 - out1], amp);
+        prop_assert!(d2 >= d0, "D {} -> {}", d0, d2);
+    }
+
+    #[test]
+    fn prop_imbalanced_n_round_trip_never_dilutes_lps(
+        pool in prop::collection::vec((balance(), prop_oneof![Just(0u64), balance()]), 2..=MAX_TOKENS),
+        amp in amp(),
+        fee_bps in 0u64..=100,
+    ) {
+        let (balances, amounts): (Vec<u64>, Vec<u64>) = pool.into_iter().unzip();
+        let supply = *balances.iter().max().unwrap();
+        let Ok(lp) = try_calc_lp_tokens_imbalanced_n(&amounts, &balances, supply, amp, fee_bps)
+        else {
+            return Ok(());
+        };
+        let new: Vec<u64> = balances.iter().zip(&amounts).map(|(b, a)| b + a).collect();
+        let Ok(out) = try_calc_withdraw_n(lp, &new, supply + lp) else { return Ok(()) };
+
+        let remaining: Vec<u64> = new.iter().zip(&out).map(|(b, o)| b - o).collect();
+        let d0 = reference::calc_d(&balances, amp);
+        let d2 = reference::calc_d(&remaining, amp);
+        prop_assert!(d2 >= d0, "D {} -> {}", d0, d2);
+    }
+
+    /// The 256-bit path must return exactly what the u128 path returns
+    /// wherever the latter succeeds. Hand-picked pools cannot cover the
+    /// rounding near the u64 limit, so this samples the whole range.
+    #[test]
+    fn prop_wide_matches_u128(
+        x in 1u64..1 << 62,
+        y in 1u64..1 << 62,
+        dx in 0u64..1 << 40,
+        amp in MIN_AMP..=MAX_AMP,
+    ) {
+        if let Ok(d) = try_calc_d(x, y, amp) {
+            prop_assert_eq!(calc_d_wide(x, y, amp), Ok(d as u128));
+
+            let x_new = x + dx;
+            if let Ok(new_y) = try_calc_y(x_new, d, amp) {
+                prop_assert_eq!(calc_y_wide(x_new, d as u128, amp), Ok(new_y));
+            }
+
+            let vp = try_calc_virtual_price(x, y, x / 2 + 1, amp);
+            prop_assert_eq!(calc_virtual_price_wide(x, y, x / 2 + 1, amp), vp);
+        }
+    }
+}
@@ -0,0 +1,1063 @@
+//! Swap accounting with the admin fee split
+//!
+//! `math::simulate_swap` only answers "how much comes out". These functions
+//! also say where the fee goes, following this SDK's model of the swap
+//! handler:
+//!
+//! 1. `d = calc_d(bal_in, bal_out)`, `y = calc_y(bal_in + amount_in, d)`
+//! 2. `gross = bal_out - y` (curve output, rounded down by the solver)
+//! 3. `fee = gross * fee_bps / 10000` (rounded down)
+//! 4. `admin_fee = fee * ADMIN_FEE_PCT / 100` (rounded down, accrues to
+//!    `admin_fee{in,out}` and leaves the pool balance)
+//! 5. `lp_fee = fee - admin_fee` (stays in the pool, raising D)
+//! 6. `amount_out = gross - fee`
+//! 7. `new_bal_out = bal_out - amount_out - admin_fee`
+//!
+//! The program's source is not available to this crate, so none of these
+//! steps, in particular taking the admin fee out of the fee (4) rather than
+//! out of `gross` and deducting it from the pool balance (7), is sourced
+//! from the handler. Parity is unverified until swaps the program executed
+//! are recorded in `parity/program_vectors.csv`; until then
+//! `test_program_vectors` is ignored and fails when run.
+
+use crate::constants::ADMIN_FEE_PCT;
+use crate::error::MathError;
+use crate::math;
+
+/// Full result of a swap, with the fee split between LPs and the admin
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SwapResult {
+    /// Tokens sent to the user
+    pub amount_out: u64,
+    /// Fee retained by LPs (stays in `new_bal_out`)
+    pub lp_fee: u64,
+    /// Fee accrued to the admin (removed from `new_bal_out`)
+    pub admin_fee: u64,
+    pub new_bal_in: u64,
+    pub new_bal_out: u64,
+}
+
+/// Swap `amount_in` into a 2-token pool
+pub fn swap(
+    bal_in: u64,
+    bal_out: u64,
+    amount_in: u64,
+    amp: u64,
+    fee_bps: u64,
+) -> Option<SwapResult> {
+    try_swap(bal_in, bal_out, amount_in, amp, fee_bps).ok()
+}
+
+/// `swap` with a typed error
+pub fn try_swap(
+    bal_in: u64,
+    bal_out: u64,
+    amount_in: u64,
+    amp: u64,
+    fee_bps: u64,
+) -> Result<SwapResult, MathError> {
+    if amount_in == 0 {
+        return Err(MathError::ZeroAmount);
+    }
+
+    let d = math::try_calc_d(bal_in, bal_out, amp)?;
+    let new_bal_in = bal_in.checked_add(amount_in).ok_or(MathError::Overflow)?;
+    let y = math::try_calc_y(new_bal_in, d, amp)?;
+    let gross = bal_out
+        .checked_sub(y)
+        .ok_or(MathError::InsufficientLiquidity)?;
+
+    apply_fees(gross, bal_out, new_bal_in, fee_bps)
+}
+
+/// Swap `amount_in` of token `i` for token `j` in an N-token pool
+pub fn swap_n(
+    balances: &[u64],
+    i: usize,
+    j: usize,
+    amount_in: u64,
+    amp: u64,
+    fee_bps: u64,
+) -> Option<SwapResult> {
+    try_swap_n(balances, i, j, amount_in, amp, fee_bps).ok()
+}
+
+/// `swap_n` with a typed error
+pub fn try_swap_n(
+    balances: &[u64],
+    i: usize,
+    j: usize,
+    amount_in: u64,
+    amp: u64,
+    fee_bps: u64,
+) -> Result<SwapResult, MathError> {
+    if amount_in == 0 {
+        return Err(MathError::ZeroAmount);
+    }
+
+    let bal_out = *balances.get(j).ok_or(MathError::InvalidToken)?;
+    let new_bal_in = balances
+        .get(i)
+        .ok_or(MathError::InvalidToken)?
+        .checked_add(amount_in)
+        .ok_or(MathError::Overflow)?;
+    let y = math::try_calc_y_n(balances, i, j, new_bal_in, amp)?;
+    let gross = bal_out
+        .checked_sub(y)
+        .ok_or(MathError::InsufficientLiquidity)?;
+
+    apply_fees(gross, bal_out, new_bal_in, fee_bps)
+}
+
+fn apply_fees(
+    gross: u64,
+    bal_out: u64,
+    new_bal_in: u64,
+    fee_bps: u64,
+) -> Result<SwapResult, MathError> {
+    let fee =
+        u64::try_from(gross as u128 * fee_bps as u128 / 10000).map_err(|_| MathError::Overflow)?;
+    let admin_fee = (fee as u128 * ADMIN_FEE_PCT as u128 / 100) as u64;
+    let lp_fee = fee - admin_fee;
+    let amount_out = gross.checked_sub(fee).ok_or(MathError::Overflow)?;
+
+    let new_bal_out = bal_out
+        .checked_sub(amount_out)
+        .and_then(|b| b.checked_sub(admin_fee))
+        .ok_or(MathError::InsufficientLiquidity)?;
+
+    Ok(SwapResult {
+        amount_out,
+        lp_fee,
+        admin_fee,
+        new_bal_in,
+        new_bal_out,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// (bal_in, bal_out, amount_in, amp, fee_bps,
+    ///  amount_out, lp_fee, admin_fee, new_bal_in, new_bal_out)
+    type SwapVector = (u64, u64, u64, u64, u64, u64, u64, u64, u64, u64);
+
+    /// (balances, i, j, amount_in, amp,
+    ///  amount_out, lp_fee, admin_fee, new_bal_in, new_bal_out), fee_bps = 30
+    type SwapNVector = ([u64; 3], usize, usize, u64, u64, u64, u64, u64, u64, u64);
+
+    // Regression pins: outputs of this module itself, so a refactor cannot
+    // silently change what users are quoted. They say nothing about parity
+    // with the program; `program_vectors.csv` does.
+    const REGRESSION_SWAP: &[SwapVector] = &[
+        (
+            1000000000000,
+            1000000000000,
+            100000,
+            1,
+            4,
+            99960,
+            20,
+            20,
+            1000000100000,
+            999999900020,
+        ),
+        (
+            1000000000000,
+            1000000000000,
+            333333333333,
+            1,
+            4,
+            298530666026,
+            59730025,
+            59730025,
+            1333333333333,
+            701409603949,
+        ),
+        (
+            1000000000000,
+            1000000000000,
+            100000,
+            1,
+            30,
+            99700,
+            150,
+            150,
+            1000000100000,
+            999999900150,
+        ),
+        (
+            1000000000000,
+            1000000000000,
+            333333333333,
+            1,
+            30,
+            297754175698,
+            447975189,
+            447975189,
+            1333333333333,
+            701797849113,
+        ),
+        (
+            1000000000000,
+            1000000000000,
+            100000,
+            100,
+            4,
+            99960,
+            20,
+            20,
+            1000000100000,
+            999999900020,
+        ),
+        (
+            1000000000000,
+            1000000000000,
+            333333333333,
+            100,
+            4,
+            332580472409,
+            66542712,
+            66542711,
+            1333333333333,
+            667352984880,
+        ),
+        (
+            1000000000000,
+            1000000000000,
+            100000,
+            100,
+            30,
+            99700,
+            150,
+            150,
+            1000000100000,
+            999999900150,
+        ),
+        (
+            1000000000000,
+            1000000000000,
+            333333333333,
+            100,
+            30,
+            331715417159,
+            499070337,
+            499070336,
+            1333333333333,
+            667785512505,
+        ),
+        (
+            1000000000000,
+            1000000000000,
+            100000,
+            2000,
+            4,
+            99960,
+            20,
+            20,
+            1000000100000,
+            999999900020,
+        ),
+        (
+            1000000000000,
+            1000000000000,
+            333333333333,
+            2000,
+            4,
+            333168775673,
+            66660419,
+            66660419,
+            1333333333333,
+            666764563908,
+        ),
+        (
+            1000000000000,
+            1000000000000,
+            100000,
+            2000,
+            30,
+            99700,
+            150,
+            150,
+            1000000100000,
+            999999900150,
+        ),
+        (
+            1000000000000,
+            1000000000000,
+            333333333333,
+            2000,
+            30,
+            332302190222,
+            499953145,
+            499953144,
+            1333333333333,
+            667197856634,
+        ),
+        (
+            1000000000000,
+            1000000000000,
+            100000,
+            100000,
+            4,
+            99960,
+            20,
+            20,
+            1000000100000,
+            999999900020,
+        ),
+        (
+            1000000000000,
+            1000000000000,
+            333333333333,
+            100000,
+            4,
+            333199375256,
+            66666542,
+            66666541,
+            1333333333333,
+            666733958203,
+        ),
+        (
+            1000000000000,
+            1000000000000,
+            100000,
+            100000,
+            30,
+            99700,
+            150,
+            150,
+            1000000100000,
+            999999900150,
+        ),
+        (
+            1000000000000,
+            1000000000000,
+            333333333333,
+            100000,
+            30,
+            332332710214,
+            499999063,
+            499999062,
+            1333333333333,
+            667167290724,
+        ),
+        (
+            1000000000000,
+            250000000000,
+            100000,
+            1,
+            4,
+            54537,
+            11,
+            10,
+            1000000100000,
+            249999945453,
+        ),
+        (
+            1000000000000,
+            250000000000,
+            333333333333,
+            1,
+            4,
+            127772354319,
+            25564697,
+            25564696,
+            1333333333333,
+            122202080985,
+        ),
+        (
+            1000000000000,
+            250000000000,
+            100000,
+            1,
+            30,
+            54395,
+            82,
+            81,
+            1000000100000,
+            249999945524,
+        ),
+        (
+            1000000000000,
+            250000000000,
+            333333333333,
+            1,
+            30,
+            127440013261,
+            191735226,
+            191735225,
+            1333333333333,
+            122368251514,
+        ),
+        (
+            1000000000000,
+            250000000000,
+            100000,
+            100,
+            4,
+            98530,
+            20,
+            19,
+            1000000100000,
+            249999901451,
+        ),
+        (
+            1000000000000,
+            250000000000,
+            333333333333,
+            100,
+            4,
+            240556988407,
+            48130650,
+            48130649,
+            1333333333333,
+            9394880944,
+        ),
+        (
+            1000000000000,
+            250000000000,
+            100000,
+            100,
+            30,
+            98274,
+            148,
+            147,
+            1000000100000,
+            249999901579,
+        ),
+        (
+            1000000000000,
+            250000000000,
+            333333333333,
+            100,
+            30,
+            239931289957,
+            360979875,
+            360979874,
+            1333333333333,
+            9707730169,
+        ),
+        (
+            1000000000000,
+            250000000000,
+            100000,
+            2000,
+            4,
+            99888,
+            20,
+            19,
+            1000000100000,
+            249999900093,
+        ),
+        (
+            1000000000000,
+            250000000000,
+            333333333333,
+            2000,
+            4,
+            249356162553,
+            49891189,
+            49891188,
+            1333333333333,
+            593946259,
+        ),
+        (
+            1000000000000,
+            250000000000,
+            100000,
+            2000,
+            30,
+            99628,
+            150,
+            149,
+            1000000100000,
+            249999900223,
+        ),
+        (
+            1000000000000,
+            250000000000,
+            333333333333,
+            2000,
+            30,
+            248707577096,
+            374183917,
+            374183917,
+            1333333333333,
+            918238987,
+        ),
+        (
+            1000000000000,
+            250000000000,
+            100000,
+            100000,
+            4,
+            99960,
+            20,
+            19,
+            1000000100000,
+            249999900021,
+        ),
+        (
+            1000000000000,
+            250000000000,
+            333333333333,
+            100000,
+            4,
+            249889020204,
+            49997803,
+            49997803,
+            1333333333333,
+            60981993,
+        ),
+        (
+            1000000000000,
+            250000000000,
+            100000,
+            100000,
+            30,
+            99700,
+            150,
+            149,
+            1000000100000,
+            249999900151,
+        ),
+        (
+            1000000000000,
+            250000000000,
+            333333333333,
+            100000,
+            30,
+            249239048763,
+            374983524,
+            374983523,
+            1333333333333,
+            385967714,
+        ),
+        (
+            37000000000,
+            5000000000000,
+            100000,
+            1,
+            4,
+            5541576,
+            1109,
+            1108,
+            37000100000,
+            4999994457316,
+        ),
+        (
+            37000000000,
+            5000000000000,
+            12333333333,
+            1,
+            4,
+            549520649161,
+            109948109,
+            109948109,
+            49333333333,
+            4450369402730,
+        ),
+        (
+            37000000000,
+            5000000000000,
+            100000,
+            1,
+            30,
+            5527162,
+            8316,
+            8315,
+            37000100000,
+            4999994464523,
+        ),
+        (
+            37000000000,
+            5000000000000,
+            12333333333,
+            1,
+            30,
+            548091323743,
+            824610818,
+            824610818,
+            49333333333,
+            4451084065439,
+        ),
+        (
+            37000000000,
+            5000000000000,
+            100000,
+            100,
+            4,
+            978032,
+            196,
+            195,
+            37000100000,
+            4999999021773,
+        ),
+        (
+            37000000000,
+            5000000000000,
+            12333333333,
+            100,
+            4,
+            94810524797,
+            18969693,
+            18969692,
+            49333333333,
+            4905170505511,
+        ),
+        (
+            37000000000,
+            5000000000000,
+            100000,
+            100,
+            30,
+            975488,
+            1468,
+            1467,
+            37000100000,
+            4999999023045,
+        ),
+        (
+            37000000000,
+            5000000000000,
+            12333333333,
+            100,
+            30,
+            94563918790,
+            142272696,
+            142272696,
+            49333333333,
+            4905293808514,
+        ),
+        (
+            37000000000,
+            5000000000000,
+            100000,
+            2000,
+            4,
+            156896,
+            31,
+            31,
+            37000100000,
+            4999999843073,
+        ),
+        (
+            37000000000,
+            5000000000000,
+            12333333333,
+            2000,
+            4,
+            17600303802,
+            3521469,
+            3521469,
+            49333333333,
+            4982396174729,
+        ),
+        (
+            37000000000,
+            5000000000000,
+            100000,
+            2000,
+            30,
+            156488,
+            235,
+            235,
+            37000100000,
+            4999999843277,
+        ),
+        (
+            37000000000,
+            5000000000000,
+            12333333333,
+            2000,
+            30,
+            17554524700,
+            26411020,
+            26411020,
+            49333333333,
+            4982419064280,
+        ),
+        (
+            37000000000,
+            5000000000000,
+            100000,
+            100000,
+            4,
+            101119,
+            20,
+            20,
+            37000100000,
+            4999999898861,
+        ),
+        (
+            37000000000,
+            5000000000000,
+            12333333333,
+            100000,
+            4,
+            12435458219,
+            2488087,
+            2488086,
+            49333333333,
+            4987562053695,
+        ),
+        (
+            37000000000,
+            5000000000000,
+            100000,
+            100000,
+            30,
+            100856,
+            152,
+            151,
+            37000100000,
+            4999999898993,
+        ),
+        (
+            37000000000,
+            5000000000000,
+            12333333333,
+            100000,
+            30,
+            12403113089,
+            18660652,
+            18660651,
+            49333333333,
+            4987578226260,
+        ),
+        (
+            100000000, 100000000, 100000, 1, 4, 99928, 20, 19, 100100000, 99900053,
+        ),
+        (
+            100000000, 100000000, 33333333, 1, 4, 29853067, 5973, 5973, 133333333, 70140960,
+        ),
+        (
+            100000000, 100000000, 100000, 1, 30, 99668, 150, 149, 100100000, 99900183,
+        ),
+        (
+            100000000, 100000000, 33333333, 1, 30, 29775418, 44798, 44797, 133333333, 70179785,
+        ),
+        (
+            100000000, 100000000, 100000, 100, 4, 99960, 20, 20, 100100000, 99900020,
+        ),
+        (
+            100000000, 100000000, 33333333, 100, 4, 33258048, 6654, 6654, 133333333, 66735298,
+        ),
+        (
+            100000000, 100000000, 100000, 100, 30, 99700, 150, 150, 100100000, 99900150,
+        ),
+        (
+            100000000, 100000000, 33333333, 100, 30, 33171542, 49907, 49907, 133333333, 66778551,
+        ),
+        (
+            100000000, 100000000, 100000, 2000, 4, 99960, 20, 20, 100100000, 99900020,
+        ),
+        (
+            100000000, 100000000, 33333333, 2000, 4, 33316878, 6666, 6666, 133333333, 66676456,
+        ),
+        (
+            100000000, 100000000, 100000, 2000, 30, 99700, 150, 150, 100100000, 99900150,
+        ),
+        (
+            100000000, 100000000, 33333333, 2000, 30, 33230220, 49995, 49995, 133333333, 66719785,
+        ),
+        (
+            100000000, 100000000, 100000, 100000, 4, 99960, 20, 20, 100100000, 99900020,
+        ),
+        (
+            100000000, 100000000, 33333333, 100000, 4, 33319938, 6667, 6666, 133333333, 66673396,
+        ),
+        (
+            100000000, 100000000, 100000, 100000, 30, 99700, 150, 150, 100100000, 99900150,
+        ),
+        (
+            100000000, 100000000, 33333333, 100000, 30, 33233272, 50000, 49999, 133333333, 66716729,
+        ),
+    ];
+
+    const REGRESSION_SWAP_N: &[SwapNVector] = &[
+        (
+            [1000000000000, 1000000000000, 1000000000000],
+            0,
+            2,
+            1000000000,
+            10,
+            996989044,
+            1499984,
+            1499983,
+            1001000000000,
+            999001510973,
+        ),
+        (
+            [1000000000000, 1000000000000, 1000000000000],
+            2,
+            1,
+            70000000000,
+            10,
+            69736098718,
+            104918905,
+            104918904,
+            1070000000000,
+            930158982378,
+        ),
+        (
+            [1000000000000, 1000000000000, 1000000000000],
+            0,
+            2,
+            1000000000,
+            1000,
+            996999890,
+            1500000,
+            1499999,
+            1001000000000,
+            999001500111,
+        ),
+        (
+            [1000000000000, 1000000000000, 1000000000000],
+            2,
+            1,
+            70000000000,
+            1000,
+            69789454583,
+            104999179,
+            104999179,
+            1070000000000,
+            930105546238,
+        ),
+        (
+            [500000000000, 2000000000000, 80000000000],
+            0,
+            2,
+            1000000000,
+            10,
+            606930410,
+            913135,
+            913135,
+            501000000000,
+            79392156455,
+        ),
+        (
+            [500000000000, 2000000000000, 80000000000],
+            2,
+            1,
+            70000000000,
+            10,
+            100028724724,
+            150494571,
+            150494570,
+            150000000000,
+            1899820780706,
+        ),
+        (
+            [500000000000, 2000000000000, 80000000000],
+            0,
+            2,
+            1000000000,
+            1000,
+            989028665,
+            1488007,
+            1488007,
+            501000000000,
+            79009483328,
+        ),
+        (
+            [500000000000, 2000000000000, 80000000000],
+            2,
+            1,
+            70000000000,
+            1000,
+            70128404803,
+            105509135,
+            105509134,
+            150000000000,
+            1929766086063,
+        ),
+    ];
+
+    /// Swaps captured from the program; see the file header for provenance
+    const PROGRAM_VECTORS: &str = include_str!("parity/program_vectors.csv");
+
+    /// Accepted `source` prefixes, each naming a run of the program itself
+    const PROVENANCE: &[&str] = &["program-test:", "mainnet:", "devnet:"];
+
+    /// One captured swap: inputs, then the observed `SwapResult` fields
+    /// (`None` where the capture could not observe one)
+    struct ProgramVector {
+        source: String,
+        balances: Vec<u64>,
+        i: usize,
+        j: usize,
+        amount_in: u64,
+        amp: u64,
+        fee_bps: u64,
+        observed: [(&'static str, Option<u64>); 5],
+    }
+
+    fn parse_program_vectors(csv: &str) -> Vec<ProgramVector> {
+        csv.lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| {
+                let cols: Vec<&str> = line.split(',').map(str::trim).collect();
+                assert_eq!(cols.len(), 12, "malformed vector: {}", line);
+                let num = |col: &str| {
+                    col.parse::<u64>()
+                        .unwrap_or_else(|_| panic!("bad number in: {}", line))
+                };
+                let observed = |col: &str| (col != "-").then(|| num(col));
+
+                ProgramVector {
+                    source: cols[0].to_string(),
+                    balances: cols[1].split(';').map(num).collect(),
+                    i: num(cols[2]) as usize,
+                    j: num(cols[3]) as usize,
+                    amount_in: num(cols[4]),
+                    amp: num(cols[5]),
+                    fee_bps: num(cols[6]),
+                    observed: [
+                        ("amount_out", observed(cols[7])),
+                        ("lp_fee", observed(cols[8])),
+                        ("admin_fee", observed(cols[9])),
+                        ("new_bal_in", observed(cols[10])),
+                        ("new_bal_out", observed(cols[11])),
+                    ],
+                }
+            })
+            .collect()
+    }
+
+    fn replay(v: &ProgramVector) -> Option<SwapResult> {
+        if v.balances.len() == 2 {
+            swap(
+                v.balances[v.i],
+                v.balances[v.j],
+                v.amount_in,
+                v.amp,
+                v.fee_bps,
+            )
+        } else {
+            swap_n(&v.balances, v.i, v.j, v.amount_in, v.amp, v.fee_bps)
+        }
+    }
+
+    #[test]
+    #[ignore = "no program captures are checked in; see parity/program_vectors.csv"]
+    fn test_program_vectors() {
+        let vectors = parse_program_vectors(PROGRAM_VECTORS);
+        assert!(
+            !vectors.is_empty(),
+            "no program captures: parity with the program is unverified"
+        );
+
+        for v in vectors {
+            assert!(
+                PROVENANCE.iter().any(|p| v.source.starts_with(p)),
+                "{}: source must name a program run",
+                v.source
+            );
+
+            let r =
+                replay(&v).unwrap_or_else(|| panic!("{}: program swapped, SDK refused", v.source));
+            let replayed = [
+                r.amount_out,
+                r.lp_fee,
+                r.admin_fee,
+                r.new_bal_in,
+                r.new_bal_out,
+            ];
+            for ((field, observed), sdk) in v.observed.iter().zip(replayed) {
+                if let Some(observed) = observed {
+                    assert_eq!(sdk, *observed, "{}: {}", v.source, field);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_program_vector_format() {
+        let rows = parse_program_vectors(
+            "# comment\n\
+             devnet:sig,1000;2000;3000,2,0,5,100,30,4,-,1,6,7\n",
+        );
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].source, "devnet:sig");
+        assert_eq!(rows[0].balances, vec![1000, 2000, 3000]);
+        assert_eq!((rows[0].i, rows[0].j, rows[0].amount_in), (2, 0, 5));
+        assert_eq!(rows[0].observed[0], ("amount_out", Some(4)));
+        assert_eq!(rows[0].observed[1], ("lp_fee", None));
+    }
+
+    #[test]
+    fn test_regression_swap_vectors() {
+        for &(
+            bal_in,
+            bal_out,
+            amount_in,
+            amp,
+            fee_bps,
+            amount_out,
+            lp_fee,
+            admin_fee,
+            new_bal_in,
+            new_bal_out,
+        ) in REGRESSION_SWAP
+        {
+            let expected = SwapResult {
+                amount_out,
+                lp_fee,
+                admin_fee,
+                new_bal_in,
+                new_bal_out,
+            };
+            assert_eq!(
+                swap(bal_in, bal_out, amount_in, amp, fee_bps),
+                Some(expected)
+            );
+        }
+    }
+
+    #[test]
+    fn test_regression_swap_n_vectors() {
+        for &(
+            balances,
+            i,
+            j,
+            amount_in,
+            amp,
+            amount_out,
+            lp_fee,
+            admin_fee,
+            new_bal_in,
+            new_bal_out,
+        ) in REGRESSION_SWAP_N
+        {
+            let expected = SwapResult {
+                amount_out,
+                lp_fee,
+                admin_fee,
+                new_bal_in,
+                new_bal_out,
+            };
+            assert_eq!(swap_n(&balances, i, j, amount_in, amp, 30), Some(expected));
+        }
+    }
+
+    #[test]
+    fn test_matches_simulate_swap() {
+        for &(bal_in, bal_out, amount_in, amp, fee_bps, ..) in REGRESSION_SWAP {
+            let result = swap(bal_in, bal_out, amount_in, amp, fee_bps).unwrap();
+            assert_eq!(
+                Some(result.amount_out),
+                math::simulate_swap(bal_in, bal_out, amount_in, amp, fee_bps)
+            );
+
+            // Every unit leaving the pool balance is accounted for
+            assert_eq!(
+                bal_out - result.new_bal_out,
+                result.amount_out + result.admin_fee
+            );
+        }
+    }
+}
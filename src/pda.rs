@@ -0,0 +1,285 @@
+//! Program-derived address helpers
+//!
+//! Each `find_*` searches for the canonical bump; each `create_*` derives
+//! the address for a bump already known (e.g. read from account state).
+//!
+//! The seed layouts are not sourced: neither this crate nor any published
+//! program documentation defines them, and no deployed account has been
+//! checked against them. Verify a derived address against the account the
+//! program actually created (e.g. `pda::create_pool_address` with
+//! `pool.bump` must give the pool's own key) before relying on it.
+
+use solana_program::pubkey::{Pubkey, PubkeyError};
+
+use crate::constants::{MAX_TOKENS, PROGRAM_ID};
+
+// Assumed seed prefixes; unverified against the program (see module doc)
+pub const POOL_SEED: &[u8] = b"pool";
+pub const NPOOL_SEED: &[u8] = b"npool";
+pub const VAULT_SEED: &[u8] = b"vault";
+pub const LP_MINT_SEED: &[u8] = b"lp_mint";
+pub const FARM_SEED: &[u8] = b"farm";
+pub const USER_FARM_SEED: &[u8] = b"user_farm";
+pub const LOTTERY_SEED: &[u8] = b"lottery";
+pub const LOTTERY_ENTRY_SEED: &[u8] = b"lottery_entry";
+pub const REGISTRY_SEED: &[u8] = b"registry";
+
+// ============================================================================
+// Pools
+// ============================================================================
+
+/// 2-token pool: `["pool", mint0, mint1]`
+pub fn find_pool_address(mint0: &Pubkey, mint1: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[POOL_SEED, mint0.as_ref(), mint1.as_ref()], &PROGRAM_ID)
+}
+
+pub fn create_pool_address(
+    mint0: &Pubkey,
+    mint1: &Pubkey,
+    bump: u8,
+) -> Result<Pubkey, PubkeyError> {
+    Pubkey::create_program_address(
+        &[POOL_SEED, mint0.as_ref(), mint1.as_ref(), &[bump]],
+        &PROGRAM_ID,
+    )
+}
+
+/// N-token pool: `["npool", mint_0, .., mint_n-1]`.
+///
+/// Returns `None` unless there are 2..=`MAX_TOKENS` mints.
+pub fn find_npool_address(mints: &[Pubkey]) -> Option<(Pubkey, u8)> {
+    if !(2..=MAX_TOKENS).contains(&mints.len()) {
+        return None;
+    }
+    Pubkey::try_find_program_address(&npool_seeds(mints), &PROGRAM_ID)
+}
+
+pub fn create_npool_address(mints: &[Pubkey], bump: u8) -> Result<Pubkey, PubkeyError> {
+    if !(2..=MAX_TOKENS).contains(&mints.len()) {
+        return Err(PubkeyError::InvalidSeeds);
+    }
+    let bump = [bump];
+    let mut seeds = npool_seeds(mints);
+    seeds.push(&bump);
+    Pubkey::create_program_address(&seeds, &PROGRAM_ID)
+}
+
+fn npool_seeds(mints: &[Pubkey]) -> Vec<&[u8]> {
+    let mut seeds = Vec::with_capacity(mints.len() + 2);
+    seeds.push(NPOOL_SEED);
+    seeds.extend(mints.iter().map(|m| m.as_ref()));
+    seeds
+}
+
+/// Pool vault: `["vault", pool, mint]`
+pub fn find_vault_address(pool: &Pubkey, mint: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[VAULT_SEED, pool.as_ref(), mint.as_ref()], &PROGRAM_ID)
+}
+
+pub fn create_vault_address(pool: &Pubkey, mint: &Pubkey, bump: u8) -> Result<Pubkey, PubkeyError> {
+    Pubkey::create_program_address(
+        &[VAULT_SEED, pool.as_ref(), mint.as_ref(), &[bump]],
+        &PROGRAM_ID,
+    )
+}
+
+/// Pool LP mint: `["lp_mint", pool]`
+pub fn find_lp_mint_address(pool: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[LP_MINT_SEED, pool.as_ref()], &PROGRAM_ID)
+}
+
+pub fn create_lp_mint_address(pool: &Pubkey, bump: u8) -> Result<Pubkey, PubkeyError> {
+    Pubkey::create_program_address(&[LP_MINT_SEED, pool.as_ref(), &[bump]], &PROGRAM_ID)
+}
+
+// ============================================================================
+// Farming
+// ============================================================================
+
+/// Farm: `["farm", pool, reward_mint]`
+pub fn find_farm_address(pool: &Pubkey, reward_mint: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[FARM_SEED, pool.as_ref(), reward_mint.as_ref()],
+        &PROGRAM_ID,
+    )
+}
+
+pub fn create_farm_address(
+    pool: &Pubkey,
+    reward_mint: &Pubkey,
+    bump: u8,
+) -> Result<Pubkey, PubkeyError> {
+    Pubkey::create_program_address(
+        &[FARM_SEED, pool.as_ref(), reward_mint.as_ref(), &[bump]],
+        &PROGRAM_ID,
+    )
+}
+
+/// User farm position: `["user_farm", farm, owner]`
+pub fn find_user_farm_address(farm: &Pubkey, owner: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[USER_FARM_SEED, farm.as_ref(), owner.as_ref()],
+        &PROGRAM_ID,
+    )
+}
+
+pub fn create_user_farm_address(
+    farm: &Pubkey,
+    owner: &Pubkey,
+    bump: u8,
+) -> Result<Pubkey, PubkeyError> {
+    Pubkey::create_program_address(
+        &[USER_FARM_SEED, farm.as_ref(), owner.as_ref(), &[bump]],
+        &PROGRAM_ID,
+    )
+}
+
+// ============================================================================
+// Lottery
+// ============================================================================
+
+/// Lottery: `["lottery", pool]`
+pub fn find_lottery_address(pool: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[LOTTERY_SEED, pool.as_ref()], &PROGRAM_ID)
+}
+
+pub fn create_lottery_address(pool: &Pubkey, bump: u8) -> Result<Pubkey, PubkeyError> {
+    Pubkey::create_program_address(&[LOTTERY_SEED, pool.as_ref(), &[bump]], &PROGRAM_ID)
+}
+
+/// Lottery entry: `["lottery_entry", lottery, owner]`
+pub fn find_lottery_entry_address(lottery: &Pubkey, owner: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[LOTTERY_ENTRY_SEED, lottery.as_ref(), owner.as_ref()],
+        &PROGRAM_ID,
+    )
+}
+
+pub fn create_lottery_entry_address(
+    lottery: &Pubkey,
+    owner: &Pubkey,
+    bump: u8,
+) -> Result<Pubkey, PubkeyError> {
+    Pubkey::create_program_address(
+        &[
+            LOTTERY_ENTRY_SEED,
+            lottery.as_ref(),
+            owner.as_ref(),
+            &[bump],
+        ],
+        &PROGRAM_ID,
+    )
+}
+
+// ============================================================================
+// Registry
+// ============================================================================
+
+/// Registry: `["registry"]`
+pub fn find_registry_address() -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[REGISTRY_SEED], &PROGRAM_ID)
+}
+
+pub fn create_registry_address(bump: u8) -> Result<Pubkey, PubkeyError> {
+    Pubkey::create_program_address(&[REGISTRY_SEED, &[bump]], &PROGRAM_ID)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_program::pubkey;
+
+    const USDC: Pubkey = pubkey!("EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v");
+    const USDT: Pubkey = pubkey!("Es9vMFrzaCERmJfrF4H2FYD4KCoNkY9cPyHGdGvYb4n");
+    const PYUSD: Pubkey = pubkey!("2b1kV6DkPAnxd5ixfnxCpjxmKwqjjaYmCZfHsFu24GXo");
+    const OWNER: Pubkey = pubkey!("11111111111111111111111111111112");
+
+    // Regression pins: outputs of this module itself, so a refactor cannot
+    // silently change the derivations. They are not accounts read from the
+    // program and say nothing about whether the seeds match it.
+    const POOL: Pubkey = pubkey!("3YmaqXFTGdSDt9DYLYo64MewU8X6QpVXYhYxfGcY2Bi1");
+    const FARM: Pubkey = pubkey!("FXosnVZeydVAsnZARSK4kVfzKjin7qxYN1qju5EsHnsP");
+    const LOTTERY: Pubkey = pubkey!("FbK7h1JfGQD6LzrtZ8KpQ4g1skWkRyvyYRnzRb5MwZHQ");
+
+    #[test]
+    fn test_regression_addresses() {
+        assert_eq!(find_pool_address(&USDC, &USDT), (POOL, 255));
+        assert_eq!(
+            find_npool_address(&[USDC, USDT, PYUSD]),
+            Some((pubkey!("AatfEsANg2senKZXggL2s81GKZmebvv6NChNoEJgsoxD"), 251))
+        );
+        assert_eq!(
+            find_vault_address(&POOL, &USDC),
+            (pubkey!("2uCHh8BQPYScBbymXVk2R4pDari59JnY43heNNzsfUJK"), 253)
+        );
+        assert_eq!(
+            find_lp_mint_address(&POOL),
+            (pubkey!("GusYnz5vo7nWX8HjMZVvGritsidSx9FzTgjgjkYFSA87"), 255)
+        );
+        assert_eq!(find_farm_address(&POOL, &USDC), (FARM, 255));
+        assert_eq!(
+            find_user_farm_address(&FARM, &OWNER),
+            (pubkey!("BbDiRCiz535oNyidau34BhyxeoSBDqUhM1bReN9wsdci"), 255)
+        );
+        assert_eq!(find_lottery_address(&POOL), (LOTTERY, 254));
+        assert_eq!(
+            find_lottery_entry_address(&LOTTERY, &OWNER),
+            (pubkey!("BEGv14i6BN5yMV7ds6QRZfBa5BJoGX7pKaWtNHNrKFMx"), 252)
+        );
+        assert_eq!(
+            find_registry_address(),
+            (pubkey!("Ez97hpqWk2ubqTsn8pv5Lv39Q4cJMKdwRPJVYDXBggJQ"), 255)
+        );
+    }
+
+    #[test]
+    fn test_create_matches_find() {
+        let (pool, bump) = find_pool_address(&USDC, &USDT);
+        assert_eq!(create_pool_address(&USDC, &USDT, bump).unwrap(), pool);
+
+        let mints = [USDC, USDT, PYUSD];
+        let (npool, bump) = find_npool_address(&mints).unwrap();
+        assert_eq!(create_npool_address(&mints, bump).unwrap(), npool);
+
+        let (vault, bump) = find_vault_address(&pool, &USDT);
+        assert_eq!(create_vault_address(&pool, &USDT, bump).unwrap(), vault);
+
+        let (lp_mint, bump) = find_lp_mint_address(&pool);
+        assert_eq!(create_lp_mint_address(&pool, bump).unwrap(), lp_mint);
+
+        let (farm, bump) = find_farm_address(&pool, &USDC);
+        assert_eq!(create_farm_address(&pool, &USDC, bump).unwrap(), farm);
+
+        let (user_farm, bump) = find_user_farm_address(&farm, &OWNER);
+        assert_eq!(
+            create_user_farm_address(&farm, &OWNER, bump).unwrap(),
+            user_farm
+        );
+
+        let (lottery, bump) = find_lottery_address(&pool);
+        assert_eq!(create_lottery_address(&pool, bump).unwrap(), lottery);
+
+        let (entry, bump) = find_lottery_entry_address(&lottery, &OWNER);
+        assert_eq!(
+            create_lottery_entry_address(&lottery, &OWNER, bump).unwrap(),
+            entry
+        );
+
+        let (registry, bump) = find_registry_address();
+        assert_eq!(create_registry_address(bump).unwrap(), registry);
+    }
+
+    #[test]
+    fn test_npool_mint_count() {
+        // 16 mints would exceed MAX_SEEDS with the prefix and bump
+        for n in [0, 1, MAX_TOKENS + 1, 16] {
+            let mints = vec![USDC; n];
+            assert_eq!(find_npool_address(&mints), None);
+            assert_eq!(
+                create_npool_address(&mints, 255),
+                Err(PubkeyError::InvalidSeeds)
+            );
+        }
+        assert!(find_npool_address(&[USDC; MAX_TOKENS]).is_some());
+    }
+}
@@ -0,0 +1,642 @@
+//! Stateful pool simulation
+//!
+//! Applies swaps, deposits, withdrawals, fee updates and amp changes to a
+//! `Pool` / `NPool` snapshot the way the program's handlers mutate the
+//! account, for replaying and backtesting sequences of operations.
+//!
+//! - Swaps go through `parity` and enforce `MIN_SWAP`
+//! - Deposits mint through the imbalance-fee quotes and enforce
+//!   `MIN_DEPOSIT` on every token deposited; the fee stays in the pool
+//! - Amp changes take two steps: `commit_amp` records `pending_amp` and
+//!   `commit_time`, and `ramp_amp` may start a ramp to that committed target
+//!   once `COMMIT_DELAY` has passed, consuming the commit
+//!
+//! Like `parity`, these rules are the SDK's model of the handlers and have
+//! not been checked against program captures.
+
+use crate::constants::{
+    COMMIT_DELAY, MAX_AMP, MAX_FEE_BPS, MAX_TOKENS, MIN_AMP, RAMP_MIN_DURATION,
+};
+use crate::error::AeX402Error;
+use crate::math;
+use crate::parity::{self, SwapResult};
+use crate::state::{NPool, Pool};
+
+/// Simulator for a 2-token pool
+#[derive(Debug, Clone)]
+pub struct Simulator {
+    pub pool: Pool,
+    /// Unix timestamp used for amp ramping
+    pub now: i64,
+}
+
+impl Simulator {
+    pub fn new(pool: Pool, now: i64) -> Self {
+        Self { pool, now }
+    }
+
+    pub fn set_time(&mut self, now: i64) {
+        self.now = now;
+    }
+
+    /// Swap `amount_in` of token `from` (0 or 1) for the other token
+    pub fn swap(
+        &mut self,
+        from: u8,
+        amount_in: u64,
+        min_out: u64,
+    ) -> Result<SwapResult, AeX402Error> {
+        self.check_not_paused()?;
+        math::check_min_swap(amount_in)?;
+
+        let amp = self.pool.try_get_amp(self.now)?;
+        let p = &mut self.pool;
+        let (bal_in, bal_out) = match from {
+            0 => (p.bal0, p.bal1),
+            1 => (p.bal1, p.bal0),
+            _ => return Err(AeX402Error::InvalidToken),
+        };
+
+        let result = parity::try_swap(bal_in, bal_out, amount_in, amp, p.fee_bps)?;
+        if result.amount_out < min_out {
+            return Err(AeX402Error::SlippageExceeded);
+        }
+
+        if from == 0 {
+            p.bal0 = result.new_bal_in;
+            p.bal1 = result.new_bal_out;
+            p.admin_fee1 = p.admin_fee1.saturating_add(result.admin_fee);
+        } else {
+            p.bal1 = result.new_bal_in;
+            p.bal0 = result.new_bal_out;
+            p.admin_fee0 = p.admin_fee0.saturating_add(result.admin_fee);
+        }
+        p.total_swaps = p.total_swaps.saturating_add(1);
+        p.total_volume = p.total_volume.saturating_add(amount_in);
+
+        Ok(result)
+    }
+
+    /// Deposit both tokens, returning LP minted
+    pub fn add_liquidity(
+        &mut self,
+        amount0: u64,
+        amount1: u64,
+        min_lp: u64,
+    ) -> Result<u64, AeX402Error> {
+        self.check_not_paused()?;
+        if amount0 == 0 && amount1 == 0 {
+            return Err(AeX402Error::ZeroAmount);
+        }
+        math::check_min_deposit(amount0)?;
+        math::check_min_deposit(amount1)?;
+
+        let amp = self.pool.try_get_amp(self.now)?;
+        let p = &mut self.pool;
+        let lp = math::try_calc_lp_tokens_imbalanced(
+            amount0,
+            amount1,
+            p.bal0,
+            p.bal1,
+            p.lp_supply,
+            amp,
+            p.fee_bps,
+        )?;
+        if lp == 0 {
+            return Err(AeX402Error::ZeroAmount);
+        }
+        if lp < min_lp {
+            return Err(AeX402Error::SlippageExceeded);
+        }
+
+        p.bal0 = p
+            .bal0
+            .checked_add(amount0)
+            .ok_or(AeX402Error::MathOverflow)?;
+        p.bal1 = p
+            .bal1
+            .checked_add(amount1)
+            .ok_or(AeX402Error::MathOverflow)?;
+        p.lp_supply = p
+            .lp_supply
+            .checked_add(lp)
+            .ok_or(AeX402Error::MathOverflow)?;
+
+        Ok(lp)
+    }
+
+    /// Burn LP for a proportional share of both tokens
+    pub fn remove_liquidity(
+        &mut self,
+        lp_amount: u64,
+        min0: u64,
+        min1: u64,
+    ) -> Result<(u64, u64), AeX402Error> {
+        if lp_amount == 0 {
+            return Err(AeX402Error::ZeroAmount);
+        }
+
+        let p = &mut self.pool;
+        if lp_amount > p.lp_supply {
+            return Err(AeX402Error::InsufficientLiquidity);
+        }
+
+        let (amount0, amount1) = math::try_calc_withdraw(lp_amount, p.bal0, p.bal1, p.lp_supply)?;
+        if amount0 < min0 || amount1 < min1 {
+            return Err(AeX402Error::SlippageExceeded);
+        }
+
+        p.bal0 -= amount0;
+        p.bal1 -= amount1;
+        p.lp_supply -= lp_amount;
+
+        Ok((amount0, amount1))
+    }
+
+    /// Set the swap fee, rejecting fees above `MAX_FEE_BPS`
+    pub fn update_fee(&mut self, fee_bps: u64) -> Result<(), AeX402Error> {
+        self.pool.fee_bps = check_fee(fee_bps)?;
+        Ok(())
+    }
+
+    /// Commit to ramping amp to `target_amp` (`CommitAmp`)
+    pub fn commit_amp(&mut self, target_amp: u64) -> Result<(), AeX402Error> {
+        let p = &mut self.pool;
+        (p.pending_amp, p.commit_time) = commit(target_amp, self.now)?;
+        Ok(())
+    }
+
+    /// Start ramping amp to the committed `target_amp` over `duration`
+    /// seconds from now, at least `COMMIT_DELAY` after `commit_amp`
+    pub fn ramp_amp(&mut self, target_amp: u64, duration: i64) -> Result<(), AeX402Error> {
+        let current = self.pool.try_get_amp(self.now)?;
+        let p = &mut self.pool;
+        check_commit(p.pending_amp, p.commit_time, target_amp, self.now)?;
+        let (amp, ramp_start, ramp_end) = start_ramp(current, target_amp, duration, self.now)?;
+
+        p.amp = amp;
+        p.target_amp = target_amp;
+        p.ramp_start = ramp_start;
+        p.ramp_end = ramp_end;
+        p.pending_amp = 0;
+
+        Ok(())
+    }
+
+    /// Freeze amp at its current ramped value
+    pub fn stop_ramp(&mut self) {
+        let current = self.pool.get_amp(self.now);
+        let p = &mut self.pool;
+
+        p.amp = current;
+        p.target_amp = current;
+        p.ramp_start = self.now;
+        p.ramp_end = self.now;
+    }
+
+    fn check_not_paused(&self) -> Result<(), AeX402Error> {
+        if self.pool.is_paused() {
+            return Err(AeX402Error::Paused);
+        }
+        Ok(())
+    }
+}
+
+/// Simulator for an N-token pool
+#[derive(Debug, Clone)]
+pub struct NSimulator {
+    pub pool: NPool,
+    /// Unix timestamp used for amp ramping
+    pub now: i64,
+}
+
+impl NSimulator {
+    pub fn new(pool: NPool, now: i64) -> Self {
+        Self { pool, now }
+    }
+
+    pub fn set_time(&mut self, now: i64) {
+        self.now = now;
+    }
+
+    /// Swap `amount_in` of token `i` for token `j`
+    pub fn swap(
+        &mut self,
+        i: usize,
+        j: usize,
+        amount_in: u64,
+        min_out: u64,
+    ) -> Result<SwapResult, AeX402Error> {
+        self.check_not_paused()?;
+        math::check_min_swap(amount_in)?;
+
+        let n = self.n_tokens();
+        if i >= n || j >= n || i == j {
+            return Err(AeX402Error::InvalidToken);
+        }
+
+        let amp = self.pool.try_get_amp(self.now)?;
+        let p = &mut self.pool;
+        let result = parity::try_swap_n(&p.balances[..n], i, j, amount_in, amp, p.fee_bps)?;
+        if result.amount_out < min_out {
+            return Err(AeX402Error::SlippageExceeded);
+        }
+
+        p.balances[i] = result.new_bal_in;
+        p.balances[j] = result.new_bal_out;
+        p.admin_fees[j] = p.admin_fees[j].saturating_add(result.admin_fee);
+        p.total_swaps = p.total_swaps.saturating_add(1);
+        p.total_volume = p.total_volume.saturating_add(amount_in);
+
+        Ok(result)
+    }
+
+    /// Deposit `amounts` (one per pool token), returning LP minted
+    pub fn add_liquidity(&mut self, amounts: &[u64], min_lp: u64) -> Result<u64, AeX402Error> {
+        self.check_not_paused()?;
+
+        let n = self.n_tokens();
+        if amounts.len() != n {
+            return Err(AeX402Error::InvalidToken);
+        }
+        if amounts.iter().all(|&a| a == 0) {
+            return Err(AeX402Error::ZeroAmount);
+        }
+        for &amount in amounts {
+            math::check_min_deposit(amount)?;
+        }
+
+        let amp = self.pool.try_get_amp(self.now)?;
+        let p = &mut self.pool;
+        let lp = math::try_calc_lp_tokens_imbalanced_n(
+            amounts,
+            &p.balances[..n],
+            p.lp_supply,
+            amp,
+            p.fee_bps,
+        )?;
+        if lp == 0 {
+            return Err(AeX402Error::ZeroAmount);
+        }
+        if lp < min_lp {
+            return Err(AeX402Error::SlippageExceeded);
+        }
+
+        for (bal, &amount) in p.balances.iter_mut().zip(amounts) {
+            *bal = bal.checked_add(amount).ok_or(AeX402Error::MathOverflow)?;
+        }
+        p.lp_supply = p
+            .lp_supply
+            .checked_add(lp)
+            .ok_or(AeX402Error::MathOverflow)?;
+
+        Ok(lp)
+    }
+
+    /// Burn LP for a proportional share of every token
+    pub fn remove_liquidity(
+        &mut self,
+        lp_amount: u64,
+        mins: &[u64],
+    ) -> Result<Vec<u64>, AeX402Error> {
+        if lp_amount == 0 {
+            return Err(AeX402Error::ZeroAmount);
+        }
+
+        let n = self.n_tokens();
+        if mins.len() != n {
+            return Err(AeX402Error::InvalidToken);
+        }
+
+        let p = &mut self.pool;
+        if lp_amount > p.lp_supply {
+            return Err(AeX402Error::InsufficientLiquidity);
+        }
+
+        let amounts = math::try_calc_withdraw_n(lp_amount, &p.balances[..n], p.lp_supply)?;
+        if amounts.iter().zip(mins).any(|(a, m)| a < m) {
+            return Err(AeX402Error::SlippageExceeded);
+        }
+
+        for (bal, &amount) in p.balances.iter_mut().zip(&amounts) {
+            *bal -= amount;
+        }
+        p.lp_supply -= lp_amount;
+
+        Ok(amounts)
+    }
+
+    /// Set the swap fee, rejecting fees above `MAX_FEE_BPS`
+    pub fn update_fee(&mut self, fee_bps: u64) -> Result<(), AeX402Error> {
+        self.pool.fee_bps = check_fee(fee_bps)?;
+        Ok(())
+    }
+
+    /// Commit to ramping amp to `target_amp` (`CommitAmp`)
+    pub fn commit_amp(&mut self, target_amp: u64) -> Result<(), AeX402Error> {
+        let p = &mut self.pool;
+        (p.pending_amp, p.commit_time) = commit(target_amp, self.now)?;
+        Ok(())
+    }
+
+    /// Start ramping amp to the committed `target_amp` over `duration`
+    /// seconds from now, at least `COMMIT_DELAY` after `commit_amp`
+    pub fn ramp_amp(&mut self, target_amp: u64, duration: i64) -> Result<(), AeX402Error> {
+        let current = self.pool.try_get_amp(self.now)?;
+        let p = &mut self.pool;
+        check_commit(p.pending_amp, p.commit_time, target_amp, self.now)?;
+        let (amp, ramp_start, ramp_end) = start_ramp(current, target_amp, duration, self.now)?;
+
+        p.amp = amp;
+        p.target_amp = target_amp;
+        p.ramp_start = ramp_start;
+        p.ramp_end = ramp_end;
+        p.pending_amp = 0;
+
+        Ok(())
+    }
+
+    /// Freeze amp at its current ramped value
+    pub fn stop_ramp(&mut self) {
+        let current = self.pool.get_amp(self.now);
+        let p = &mut self.pool;
+
+        p.amp = current;
+        p.target_amp = current;
+        p.ramp_start = self.now;
+        p.ramp_end = self.now;
+    }
+
+    fn n_tokens(&self) -> usize {
+        (self.pool.n_tokens as usize).min(MAX_TOKENS)
+    }
+
+    fn check_not_paused(&self) -> Result<(), AeX402Error> {
+        if self.pool.is_paused() {
+            return Err(AeX402Error::Paused);
+        }
+        Ok(())
+    }
+}
+
+fn check_fee(fee_bps: u64) -> Result<u64, AeX402Error> {
+    if fee_bps > MAX_FEE_BPS {
+        return Err(AeX402Error::InvalidFee);
+    }
+    Ok(fee_bps)
+}
+
+/// Validate a commit, returning `(pending_amp, commit_time)`
+fn commit(target_amp: u64, now: i64) -> Result<(u64, i64), AeX402Error> {
+    if !(MIN_AMP..=MAX_AMP).contains(&target_amp) {
+        return Err(AeX402Error::InvalidAmp);
+    }
+    Ok((target_amp, now))
+}
+
+/// A ramp must target the pending commit and start `COMMIT_DELAY` after it
+fn check_commit(
+    pending_amp: u64,
+    commit_time: i64,
+    target_amp: u64,
+    now: i64,
+) -> Result<(), AeX402Error> {
+    if pending_amp == 0 || pending_amp != target_amp {
+        return Err(AeX402Error::RampConstraint);
+    }
+
+    let ready = commit_time
+        .checked_add(COMMIT_DELAY)
+        .ok_or(AeX402Error::RampConstraint)?;
+    if now < ready {
+        return Err(AeX402Error::RampConstraint);
+    }
+    Ok(())
+}
+
+/// Validate a ramp request, returning `(amp, ramp_start, ramp_end)`
+fn start_ramp(
+    current_amp: u64,
+    target_amp: u64,
+    duration: i64,
+    now: i64,
+) -> Result<(u64, i64, i64), AeX402Error> {
+    if !(MIN_AMP..=MAX_AMP).contains(&target_amp) {
+        return Err(AeX402Error::InvalidAmp);
+    }
+    if duration < RAMP_MIN_DURATION {
+        return Err(AeX402Error::RampConstraint);
+    }
+
+    let ramp_end = now
+        .checked_add(duration)
+        .ok_or(AeX402Error::RampConstraint)?;
+    Ok((current_amp, now, ramp_end))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::{MIN_DEPOSIT, MIN_SWAP, NPOOL_SIZE, POOL_SIZE};
+    use crate::error::MathError;
+    use borsh::BorshDeserialize;
+
+    fn pool(bal: u64, amp: u64, fee_bps: u64) -> Pool {
+        let mut pool = Pool::deserialize(&mut &[0u8; POOL_SIZE][..]).unwrap();
+        pool.bal0 = bal;
+        pool.bal1 = bal;
+        pool.lp_supply = 2 * bal;
+        pool.amp = amp;
+        pool.target_amp = amp;
+        pool.fee_bps = fee_bps;
+        pool
+    }
+
+    #[test]
+    fn test_swap_updates_state() {
+        let bal = 1_000_000_000_000u64;
+        let mut sim = Simulator::new(pool(bal, 1000, 30), 0);
+
+        let result = sim.swap(0, 10_000_000_000, 0).unwrap();
+        assert_eq!(sim.pool.bal0, bal + 10_000_000_000);
+        assert_eq!(sim.pool.bal1, result.new_bal_out);
+        assert_eq!(sim.pool.admin_fee1, result.admin_fee);
+        assert_eq!(sim.pool.total_swaps, 1);
+        assert_eq!(sim.pool.total_volume, 10_000_000_000);
+
+        // Retained LP fee grows the invariant per LP token
+        let vp_before = math::calc_virtual_price(bal, bal, 2 * bal, 1000).unwrap();
+        let vp_after =
+            math::calc_virtual_price(sim.pool.bal0, sim.pool.bal1, sim.pool.lp_supply, 1000)
+                .unwrap();
+        assert!(vp_after > vp_before);
+
+        assert_eq!(
+            sim.swap(1, 1_000_000, u64::MAX),
+            Err(AeX402Error::SlippageExceeded)
+        );
+        assert_eq!(sim.swap(2, 1_000_000, 0), Err(AeX402Error::InvalidToken));
+        assert_eq!(sim.swap(0, MIN_SWAP - 1, 0), Err(AeX402Error::ZeroAmount));
+
+        // Fees outside the program's range never reach the pool
+        assert_eq!(
+            sim.update_fee(MAX_FEE_BPS + 1),
+            Err(AeX402Error::InvalidFee)
+        );
+        assert_eq!(sim.pool.fee_bps, 30);
+        sim.update_fee(4).unwrap();
+        assert_eq!(sim.pool.fee_bps, 4);
+
+        sim.pool.paused = 1;
+        assert_eq!(sim.swap(1, 1_000_000, 0), Err(AeX402Error::Paused));
+    }
+
+    #[test]
+    fn test_liquidity_round_trip() {
+        let bal = 1_000_000_000_000u64;
+        let mut sim = Simulator::new(pool(bal, 1000, 30), 0);
+
+        let lp = sim
+            .add_liquidity(10_000_000_000, 10_000_000_000, 0)
+            .unwrap();
+        let (out0, out1) = sim.remove_liquidity(lp, 0, 0).unwrap();
+        assert!(out0 <= 10_000_000_000 && out1 <= 10_000_000_000);
+        assert_eq!(sim.pool.lp_supply, 2 * bal);
+
+        assert_eq!(
+            sim.remove_liquidity(sim.pool.lp_supply + 1, 0, 0),
+            Err(AeX402Error::InsufficientLiquidity)
+        );
+
+        // Lopsided deposits pay the imbalance fee
+        let p = sim.pool.clone();
+        let fee_free =
+            math::calc_lp_tokens(10_000_000_000, 0, p.bal0, p.bal1, p.lp_supply, 1000).unwrap();
+        let lp = sim.add_liquidity(10_000_000_000, 0, 0).unwrap();
+        assert_eq!(
+            Some(lp),
+            math::calc_lp_tokens_imbalanced(
+                10_000_000_000,
+                0,
+                p.bal0,
+                p.bal1,
+                p.lp_supply,
+                1000,
+                30
+            )
+        );
+        assert!(lp < fee_free);
+
+        assert_eq!(
+            sim.add_liquidity(MIN_DEPOSIT - 1, MIN_DEPOSIT, 0),
+            Err(AeX402Error::ZeroAmount)
+        );
+        assert!(sim.add_liquidity(MIN_DEPOSIT, 0, 0).is_ok());
+    }
+
+    #[test]
+    fn test_swap_errors_keep_their_cause() {
+        // A zero amp used to surface as MathOverflow
+        let mut sim = Simulator::new(pool(1_000_000_000_000, 0, 30), 0);
+        assert_eq!(sim.swap(0, 1_000_000, 0), Err(AeX402Error::InvalidAmp));
+        assert_eq!(
+            parity::try_swap(1_000_000_000_000, 1_000_000_000_000, 1_000_000, 0, 30),
+            Err(MathError::ZeroAmp)
+        );
+    }
+
+    #[test]
+    fn test_amp_ramp() {
+        let mut sim = Simulator::new(pool(1_000_000_000_000, 1000, 30), 1_000);
+
+        // A ramp needs a commit first
+        assert_eq!(sim.ramp_amp(2000, 86_400), Err(AeX402Error::RampConstraint));
+        assert_eq!(sim.commit_amp(MAX_AMP + 1), Err(AeX402Error::InvalidAmp));
+        sim.commit_amp(2000).unwrap();
+        assert_eq!((sim.pool.pending_amp, sim.pool.commit_time), (2000, 1_000));
+
+        // ...at least COMMIT_DELAY old, for the committed target
+        sim.set_time(1_000 + COMMIT_DELAY - 1);
+        assert_eq!(sim.ramp_amp(2000, 86_400), Err(AeX402Error::RampConstraint));
+        sim.set_time(1_000 + COMMIT_DELAY);
+        assert_eq!(sim.ramp_amp(3000, 86_400), Err(AeX402Error::RampConstraint));
+        assert_eq!(sim.ramp_amp(2000, 3_600), Err(AeX402Error::RampConstraint));
+
+        let start = sim.now;
+        sim.ramp_amp(2000, 86_400).unwrap();
+        assert_eq!(sim.pool.pending_amp, 0);
+        assert_eq!(sim.ramp_amp(2000, 86_400), Err(AeX402Error::RampConstraint));
+
+        sim.set_time(start + 43_200);
+        assert_eq!(sim.pool.get_amp(sim.now), 1500);
+
+        sim.stop_ramp();
+        sim.set_time(start + 86_400);
+        assert_eq!(sim.pool.get_amp(sim.now), 1500);
+
+        // A malformed ramp that ends before it starts blocks quoting
+        sim.pool.ramp_start = sim.now + 100;
+        sim.pool.ramp_end = sim.now;
+        assert_eq!(sim.swap(0, 1_000_000, 0), Err(AeX402Error::RampConstraint));
+    }
+
+    #[test]
+    fn test_n_simulator() {
+        let bal = 1_000_000_000_000u64;
+        let mut pool = NPool::deserialize(&mut &[0u8; NPOOL_SIZE][..]).unwrap();
+        pool.n_tokens = 3;
+        pool.balances[..3].copy_from_slice(&[bal; 3]);
+        pool.lp_supply = 3 * bal;
+        pool.amp = 1000;
+        pool.target_amp = 1000;
+        pool.fee_bps = 30;
+        let mut sim = NSimulator::new(pool, 0);
+
+        let result = sim.swap(0, 2, 10_000_000_000, 0).unwrap();
+        assert_eq!(sim.pool.balances[2], result.new_bal_out);
+        assert_eq!(sim.pool.admin_fees[2], result.admin_fee);
+
+        // Fees accrued in token 2 are worth themselves in token 2, and about
+        // the same in token 0 near peg
+        let report = sim.pool.admin_fee_report(2, sim.now).unwrap();
+        assert_eq!(report.fees, vec![0, 0, result.admin_fee]);
+        assert_eq!(report.value, result.admin_fee);
+        let in_token0 = sim.pool.admin_fee_report(0, sim.now).unwrap().value;
+        assert!(in_token0.abs_diff(result.admin_fee) <= result.admin_fee / 100);
+        assert!(sim.pool.admin_fee_report(3, sim.now).is_none());
+        assert_eq!(sim.swap(0, 3, 1_000_000, 0), Err(AeX402Error::InvalidToken));
+        assert_eq!(sim.swap(1, 1, 1_000_000, 0), Err(AeX402Error::InvalidToken));
+        assert_eq!(
+            sim.add_liquidity(&[1; 2], 0),
+            Err(AeX402Error::InvalidToken)
+        );
+        assert_eq!(
+            sim.update_fee(MAX_FEE_BPS + 1),
+            Err(AeX402Error::InvalidFee)
+        );
+
+        let lp = sim.add_liquidity(&[1_000_000_000; 3], 0).unwrap();
+        let out = sim.remove_liquidity(lp, &[0; 3]).unwrap();
+        assert_eq!(out.len(), 3);
+        assert_eq!(sim.pool.lp_supply, 3 * bal);
+
+        let (balances, supply) = (sim.pool.balances, sim.pool.lp_supply);
+        let amounts = [1_000_000_000, 0, 0];
+        let lp = sim.add_liquidity(&amounts, 0).unwrap();
+        assert_eq!(
+            Some(lp),
+            math::calc_lp_tokens_imbalanced_n(&amounts, &balances[..3], supply, 1000, 30)
+        );
+        assert!(lp < math::calc_lp_tokens_n(&amounts, &balances[..3], supply, 1000).unwrap());
+        assert_eq!(
+            sim.add_liquidity(&[MIN_DEPOSIT - 1, 0, 0], 0),
+            Err(AeX402Error::ZeroAmount)
+        );
+
+        sim.commit_amp(2000).unwrap();
+        assert_eq!(sim.ramp_amp(2000, 86_400), Err(AeX402Error::RampConstraint));
+        sim.set_time(COMMIT_DELAY);
+        sim.ramp_amp(2000, 86_400).unwrap();
+        assert_eq!(sim.pool.target_amp, 2000);
+    }
+}
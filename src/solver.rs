@@ -0,0 +1,278 @@
+//! Trade-size solvers
+//!
+//! The inverse of simulation: instead of "what does this swap do", these
+//! answer "how large a swap does this". Both searches run over `amount_in`.
+//! The post-trade price falls monotonically with it; price impact only
+//! grows with it once rounding is negligible, so the impact search probes
+//! the whole range instead of stopping at the first dust-sized failure.
+//!
+//! - `max_swap_for_impact`: the largest swap whose price impact against
+//!   spot (curve only, fee excluded) stays within a budget
+//! - `swap_to_price`: the smallest swap that moves the spot price down to a
+//!   target, measured on the balances the program leaves behind
+
+use crate::math::{self, PRICE_PRECISION, U256};
+use crate::parity;
+
+/// Largest `amount_in` whose price impact is at most `max_impact_bps`.
+///
+/// Impact is `1 - gross_out / (amount_in * spot)`, the curve component of
+/// `math::PriceImpact` (the swap fee is a flat extra `fee_bps` on top).
+pub fn max_swap_for_impact(
+    bal_in: u64,
+    bal_out: u64,
+    max_impact_bps: u64,
+    amp: u64,
+) -> Option<u64> {
+    max_swap_for_impact_n(&[bal_in, bal_out], 0, 1, max_impact_bps, amp)
+}
+
+/// Largest `amount_in` of token `i` for token `j` whose price impact is at
+/// most `max_impact_bps` (N-token pool)
+pub fn max_swap_for_impact_n(
+    balances: &[u64],
+    i: usize,
+    j: usize,
+    max_impact_bps: u64,
+    amp: u64,
+) -> Option<u64> {
+    let keep = 10000u64.checked_sub(max_impact_bps)?;
+    let spot = math::spot_price_n(balances, i, j, amp)?;
+
+    // impact <= max  <=>  gross * 10000 * 1e18 >= amount_in * spot * (10000 - max)
+    let within = |amount_in: u64| {
+        let Some(gross) = math::simulate_swap_n(balances, i, j, amount_in, amp, 0) else {
+            return false;
+        };
+        let lhs = U256::from(gross) * U256::from(10000u64) * U256::from(PRICE_PRECISION);
+        let rhs = U256::from(amount_in) * U256::from(spot) * U256::from(keep);
+        lhs >= rhs
+    };
+
+    last_within(within)
+}
+
+/// Smallest `amount_in` that brings the spot price of token_in in token_out
+/// down to `target_price` (scaled by `PRICE_PRECISION`).
+///
+/// Returns `Some(0)` if the price is already at or below the target. To
+/// push the price up, solve for the opposite direction with the inverse
+/// target.
+pub fn swap_to_price(
+    bal_in: u64,
+    bal_out: u64,
+    target_price: u128,
+    amp: u64,
+    fee_bps: u64,
+) -> Option<u64> {
+    swap_to_price_n(&[bal_in, bal_out], 0, 1, target_price, amp, fee_bps)
+}
+
+/// Smallest `amount_in` of token `i` that brings the spot price of `i` in
+/// `j` down to `target_price` (N-token pool)
+pub fn swap_to_price_n(
+    balances: &[u64],
+    i: usize,
+    j: usize,
+    target_price: u128,
+    amp: u64,
+    fee_bps: u64,
+) -> Option<u64> {
+    if math::spot_price_n(balances, i, j, amp)? <= target_price {
+        return Some(0);
+    }
+
+    let reaches = |amount_in: u64| {
+        price_after_swap(balances, i, j, amount_in, amp, fee_bps)
+            .is_some_and(|price| price <= target_price)
+    };
+
+    first_within(reaches)
+}
+
+/// Spot price of `i` in `j` on the balances left after swapping `amount_in`
+pub fn price_after_swap(
+    balances: &[u64],
+    i: usize,
+    j: usize,
+    amount_in: u64,
+    amp: u64,
+    fee_bps: u64,
+) -> Option<u128> {
+    let result = parity::swap_n(balances, i, j, amount_in, amp, fee_bps)?;
+
+    let mut after = balances.to_vec();
+    after[i] = result.new_bal_in;
+    after[j] = result.new_bal_out;
+
+    math::spot_price_n(&after, i, j, amp)
+}
+
+/// Largest input for which `within` holds.
+///
+/// Integer rounding makes dust-sized quotes look far worse than the curve
+/// (a swap that quotes 0 out has 100% impact), so `within` can fail below
+/// the sizes where it holds. Every power of two is probed, and the search
+/// settles between the largest one that holds and the next, where the
+/// rounding error is at most what it was at that probe.
+fn last_within<F>(within: F) -> Option<u64>
+where
+    F: Fn(u64) -> bool,
+{
+    let Some(lo) = (0..u64::BITS)
+        .map(|k| 1u64 << k)
+        .filter(|&a| within(a))
+        .last()
+    else {
+        return Some(0);
+    };
+
+    let mut lo = lo;
+    let mut hi = match lo.checked_mul(2) {
+        Some(next) => next,
+        None if within(u64::MAX) => return Some(u64::MAX),
+        None => u64::MAX,
+    };
+
+    // Invariant: within(lo) && !within(hi)
+    while hi - lo > 1 {
+        let mid = lo + (hi - lo) / 2;
+        if within(mid) {
+            lo = mid;
+        } else {
+            hi = mid;
+        }
+    }
+
+    Some(lo)
+}
+
+/// Smallest input for which `reaches` holds, assuming it holds on a suffix
+fn first_within<F>(reaches: F) -> Option<u64>
+where
+    F: Fn(u64) -> bool,
+{
+    let mut lo = 0u64;
+    let mut hi = 1u64;
+    while !reaches(hi) {
+        lo = hi;
+        hi = hi.checked_mul(2)?;
+    }
+
+    // Invariant: !reaches(lo) && reaches(hi)
+    while hi - lo > 1 {
+        let mid = lo + (hi - lo) / 2;
+        if reaches(mid) {
+            hi = mid;
+        } else {
+            lo = mid;
+        }
+    }
+
+    Some(hi)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn impact(bal_in: u64, bal_out: u64, amount_in: u64, amp: u64) -> f64 {
+        math::RatedBalances::unit(&[bal_in, bal_out])
+            .unwrap()
+            .price_impact(0, 1, amount_in, amp, 0)
+            .unwrap()
+            .price_impact
+    }
+
+    #[test]
+    fn test_max_swap_for_impact() {
+        let cases = [
+            (1_000_000_000_000u64, 1_000_000_000_000u64, 1000u64),
+            (2_000_000_000_000, 1_000_000_000_000, 100),
+            (1_000_000_000_000, 3_000_000_000_000, 10),
+        ];
+
+        for &(bal_in, bal_out, amp) in &cases {
+            let size = max_swap_for_impact(bal_in, bal_out, 50, amp).unwrap();
+            assert!(impact(bal_in, bal_out, size, amp) <= 0.005 + 1e-9);
+            assert!(impact(bal_in, bal_out, size + size / 1000, amp) > 0.005);
+
+            // A larger budget allows a larger swap
+            assert!(max_swap_for_impact(bal_in, bal_out, 100, amp).unwrap() > size);
+        }
+
+        let balances = [1_000_000_000_000u64, 1_000_000_000_000, 1_000_000_000_000];
+        let size = max_swap_for_impact_n(&balances, 0, 2, 50, 1000).unwrap();
+        let spot =
+            math::spot_price_n(&balances, 0, 2, 1000).unwrap() as f64 / PRICE_PRECISION as f64;
+        let gross = math::simulate_swap_n(&balances, 0, 2, size, 1000, 0).unwrap();
+        assert!(size > 0 && 1.0 - gross as f64 / (size as f64 * spot) <= 0.005 + 1e-9);
+
+        assert!(max_swap_for_impact(1_000_000, 1_000_000, 10_001, 100).is_none());
+    }
+
+    #[test]
+    fn test_max_swap_for_impact_off_peg() {
+        // Unbalanced and low-amp pools, where dust quotes round far from
+        // spot long before the curve itself gets expensive
+        let cases = [
+            (10_000_000_000_000u64, 1_000_000_000_000u64, 1u64),
+            (1_000_000_000_000, 10_000_000_000_000, 1),
+            (1_000_000_000_000, 1_000_000_000_000, 1),
+            (50_000_000_000_000, 1_000_000_000_000, 10),
+            (1_000_000_000, 100_000_000_000, 1000),
+        ];
+
+        for &(bal_in, bal_out, amp) in &cases {
+            let size = max_swap_for_impact(bal_in, bal_out, 50, amp).unwrap();
+            assert!(
+                size > 1_000_000,
+                "({}, {}, {}): {}",
+                bal_in,
+                bal_out,
+                amp,
+                size
+            );
+            assert!(impact(bal_in, bal_out, size, amp) <= 0.005 + 1e-9);
+            assert!(impact(bal_in, bal_out, size + size / 1000, amp) > 0.005);
+        }
+
+        // Spot ~0.268: a 100M swap has ~0.002% impact, but 4 units quote
+        // 1 out, 7% short of spot
+        assert_eq!(
+            math::simulate_swap(10_000_000_000_000, 1_000_000_000_000, 4, 1, 0),
+            Some(1)
+        );
+        assert!(impact(10_000_000_000_000, 1_000_000_000_000, 100_000_000, 1) < 0.0001);
+        assert!(
+            max_swap_for_impact(10_000_000_000_000, 1_000_000_000_000, 50, 1).unwrap()
+                > 100_000_000
+        );
+    }
+
+    #[test]
+    fn test_swap_to_price() {
+        let bal = 1_000_000_000_000u64;
+        let target = PRICE_PRECISION / 100 * 99;
+
+        let size = swap_to_price(bal, bal, target, 100, 30).unwrap();
+        let after = price_after_swap(&[bal, bal], 0, 1, size, 100, 30).unwrap();
+        let before = price_after_swap(&[bal, bal], 0, 1, size - 1, 100, 30).unwrap();
+        assert!(after <= target && before > target);
+
+        // Already below target: nothing to trade
+        assert_eq!(
+            swap_to_price(2 * bal, bal, PRICE_PRECISION, 100, 30),
+            Some(0)
+        );
+
+        // Three-token pool
+        let balances = [bal, bal, bal];
+        let size = swap_to_price_n(&balances, 1, 0, target, 100, 30).unwrap();
+        assert!(price_after_swap(&balances, 1, 0, size, 100, 30).unwrap() <= target);
+        assert!(price_after_swap(&balances, 1, 0, size - 1, 100, 30).unwrap() > target);
+
+        // Price can never reach zero
+        assert!(swap_to_price(bal, bal, 0, 100, 30).is_none());
+    }
+}
@@ -3,16 +3,18 @@
 use borsh::{BorshDeserialize, BorshSerialize};
 use solana_program::pubkey::Pubkey;
 
-use crate::constants::{account_disc, OHLCV_24H, OHLCV_7D, MAX_TOKENS};
+use crate::constants::{account_disc, MAX_REGISTRY_POOLS, MAX_TOKENS, OHLCV_24H, OHLCV_7D};
+use crate::error::MathError;
+use crate::math;
 
 /// Delta-encoded OHLCV candle (12 bytes)
 #[derive(Debug, Clone, Copy, Default, BorshSerialize, BorshDeserialize)]
 pub struct Candle {
-    pub open: u32,      // Base price (scaled 1e6)
-    pub high_d: u16,    // High delta
-    pub low_d: u16,     // Low delta
-    pub close_d: i16,   // Close delta
-    pub volume: u16,    // Volume in 1e9 units
+    pub open: u32,    // Base price (scaled 1e6)
+    pub high_d: u16,  // High delta
+    pub low_d: u16,   // Low delta
+    pub close_d: i16, // Close delta
+    pub volume: u16,  // Volume in 1e9 units
 }
 
 impl Candle {
@@ -24,8 +26,12 @@ impl Candle {
         self.open.saturating_sub(self.low_d as u32)
     }
 
+    /// Saturates at `i32::MAX` when `open + close_d` does not fit, as it
+    /// can for the `open` values above `i32::MAX` that fuzzed account data
+    /// holds
     pub fn close(&self) -> i32 {
-        self.open as i32 + self.close_d as i32
+        let close = self.open as i64 + self.close_d as i64;
+        close.min(i32::MAX as i64) as i32
     }
 }
 
@@ -82,26 +88,59 @@ impl Pool {
 
     /// Get current effective amp (handles ramping)
     pub fn get_amp(&self, now: i64) -> u64 {
-        if now >= self.ramp_end || self.ramp_end == self.ramp_start {
-            return self.target_amp;
-        }
-        if now <= self.ramp_start {
-            return self.amp;
-        }
+        math::get_current_amp(
+            self.amp,
+            self.target_amp,
+            self.ramp_start,
+            self.ramp_end,
+            now,
+        )
+    }
 
-        let elapsed = now - self.ramp_start;
-        let duration = self.ramp_end - self.ramp_start;
+    /// `get_amp`, rejecting a ramp that ends before it starts
+    pub fn try_get_amp(&self, now: i64) -> Result<u64, MathError> {
+        math::try_get_current_amp(
+            self.amp,
+            self.target_amp,
+            self.ramp_start,
+            self.ramp_end,
+            now,
+        )
+    }
 
-        if self.target_amp > self.amp {
-            let diff = self.target_amp - self.amp;
-            self.amp + (diff * elapsed as u64) / duration as u64
-        } else {
-            let diff = self.amp - self.target_amp;
-            self.amp - (diff * elapsed as u64) / duration as u64
-        }
+    /// Balances paired with per-token `rates` (scaled by
+    /// `math::PRICE_PRECISION`) for mixed-decimal or rate-oracle pools
+    pub fn rated_balances(&self, rates: [u128; 2]) -> Option<math::RatedBalances> {
+        math::RatedBalances::new(&[self.bal0, self.bal1], &rates)
+    }
+
+    /// Summarize withdrawable admin fees, valued at the current pool price
+    pub fn admin_fee_report(&self, now: i64) -> Option<AdminFeeReport> {
+        let value_in_token1 = math::calc_value_in_token1(
+            self.admin_fee0,
+            self.admin_fee1,
+            self.bal0,
+            self.bal1,
+            self.try_get_amp(now).ok()?,
+        )?;
+
+        Some(AdminFeeReport {
+            fee0: self.admin_fee0,
+            fee1: self.admin_fee1,
+            value_in_token1,
+        })
     }
 }
 
+/// Admin fees accrued on a 2-token pool
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AdminFeeReport {
+    pub fee0: u64,
+    pub fee1: u64,
+    /// Both fees valued in token1 units at the current pool price
+    pub value_in_token1: u64,
+}
+
 /// N-token Pool state (2048 bytes)
 #[derive(Debug, Clone, BorshSerialize, BorshDeserialize)]
 pub struct NPool {
@@ -138,6 +177,70 @@ impl NPool {
     pub fn is_paused(&self) -> bool {
         self.paused != 0
     }
+
+    /// Get current effective amp (handles ramping)
+    pub fn get_amp(&self, now: i64) -> u64 {
+        math::get_current_amp(
+            self.amp,
+            self.target_amp,
+            self.ramp_start,
+            self.ramp_end,
+            now,
+        )
+    }
+
+    /// `get_amp`, rejecting a ramp that ends before it starts
+    pub fn try_get_amp(&self, now: i64) -> Result<u64, MathError> {
+        math::try_get_current_amp(
+            self.amp,
+            self.target_amp,
+            self.ramp_start,
+            self.ramp_end,
+            now,
+        )
+    }
+
+    /// Balances of the first `n_tokens` slots paired with per-token `rates`
+    pub fn rated_balances(&self, rates: &[u128]) -> Option<math::RatedBalances> {
+        let n = (self.n_tokens as usize).min(MAX_TOKENS);
+        math::RatedBalances::new(&self.balances[..n], rates)
+    }
+
+    /// Admin fees accrued per token (the first `n_tokens` slots)
+    pub fn accrued_admin_fees(&self) -> &[u64] {
+        let n = (self.n_tokens as usize).min(MAX_TOKENS);
+        &self.admin_fees[..n]
+    }
+
+    /// Summarize withdrawable admin fees, valued in token `numeraire` at
+    /// the current pool prices
+    pub fn admin_fee_report(&self, numeraire: usize, now: i64) -> Option<NAdminFeeReport> {
+        let n = (self.n_tokens as usize).min(MAX_TOKENS);
+        let fees = self.accrued_admin_fees();
+        let value = math::calc_value_in_token_n(
+            fees,
+            &self.balances[..n],
+            numeraire,
+            self.try_get_amp(now).ok()?,
+        )?;
+
+        Some(NAdminFeeReport {
+            fees: fees.to_vec(),
+            numeraire,
+            value,
+        })
+    }
+}
+
+/// Admin fees accrued on an N-token pool
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NAdminFeeReport {
+    /// Fees per token (the first `n_tokens` slots)
+    pub fees: Vec<u64>,
+    /// Token index the fees are valued in
+    pub numeraire: usize,
+    /// All fees valued in `numeraire` units at the current pool prices
+    pub value: u64,
 }
 
 /// Farm state
@@ -155,7 +258,7 @@ pub struct Farm {
     pub start_time: i64,
     pub end_time: i64,
     pub last_update: i64,
-    pub acc_reward: u128,  // scaled 1e12
+    pub acc_reward: u128, // scaled 1e12
     pub total_staked: u64,
 }
 
@@ -211,6 +314,24 @@ impl Lottery {
     pub fn is_drawn(&self) -> bool {
         self.drawn != 0
     }
+
+    /// Find the unclaimed entry holding the winning ticket.
+    ///
+    /// Entries are `(address, entry)` pairs so the result can be passed
+    /// straight to `instruction::claim_lottery`. Returns `None` until the
+    /// lottery has been drawn.
+    pub fn find_winning_entry<'a>(
+        &self,
+        entries: &'a [(Pubkey, LotteryEntry)],
+    ) -> Option<&'a (Pubkey, LotteryEntry)> {
+        if !self.is_drawn() {
+            return None;
+        }
+
+        entries.iter().find(|(_, entry)| {
+            entry.is_valid() && !entry.is_claimed() && entry.is_winner(self.winning_ticket)
+        })
+    }
 }
 
 /// Lottery entry
@@ -231,18 +352,52 @@ impl LotteryEntry {
         self.disc == account_disc::LOTENTRY
     }
 
+    pub fn is_claimed(&self) -> bool {
+        self.claimed != 0
+    }
+
     pub fn is_winner(&self, winning_ticket: u64) -> bool {
-        winning_ticket >= self.ticket_start 
-            && winning_ticket < self.ticket_start + self.ticket_count
+        winning_ticket >= self.ticket_start
+            && winning_ticket - self.ticket_start < self.ticket_count
+    }
+}
+
+/// Pool registry
+#[derive(Debug, Clone, BorshSerialize, BorshDeserialize)]
+pub struct Registry {
+    pub disc: [u8; 8],
+    pub bump: u8,
+    pub _padding: [u8; 7],
+    pub authority: Pubkey,
+    pub pending_auth: Pubkey,
+    pub auth_time: i64,
+    pub count: u32,
+    pub _padding2: [u8; 4],
+    pub pools: [Pubkey; MAX_REGISTRY_POOLS],
+}
+
+impl Registry {
+    pub fn is_valid(&self) -> bool {
+        self.disc == account_disc::REGISTRY
+    }
+
+    /// Registered pools (the first `count` slots)
+    pub fn registered_pools(&self) -> &[Pubkey] {
+        let count = (self.count as usize).min(MAX_REGISTRY_POOLS);
+        &self.pools[..count]
+    }
+
+    pub fn contains(&self, pool: &Pubkey) -> bool {
+        self.registered_pools().contains(pool)
     }
 }
 
 /// TWAP result decoded from return value
 #[derive(Debug, Clone, Copy)]
 pub struct TwapResult {
-    pub price: u32,       // Scaled 1e6
-    pub samples: u16,     // Number of candles used
-    pub confidence: u16,  // 0-10000 (0-100%)
+    pub price: u32,      // Scaled 1e6
+    pub samples: u16,    // Number of candles used
+    pub confidence: u16, // 0-10000 (0-100%)
 }
 
 impl TwapResult {
@@ -262,3 +417,172 @@ impl TwapResult {
         self.confidence as f64 / 100.0
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::REGISTRY_SIZE;
+
+    #[test]
+    fn test_candle_close() {
+        let candle = |open, close_d| Candle {
+            open,
+            close_d,
+            ..Candle::default()
+        };
+
+        assert_eq!(candle(1_000_000, -500).close(), 999_500);
+        assert_eq!(candle(1_000_000, 500).close(), 1_000_500);
+
+        // Used to overflow (a panic in debug builds, a wrapped negative
+        // price in release)
+        assert_eq!(candle(i32::MAX as u32, i16::MAX).close(), i32::MAX);
+        assert_eq!(candle(i32::MAX as u32 - 1, 1).close(), i32::MAX);
+
+        // `open as i32` wraps negative above i32::MAX
+        assert_eq!(candle(u32::MAX, 0).close(), i32::MAX);
+        assert_eq!(candle(u32::MAX, i16::MIN).close(), i32::MAX);
+        assert_eq!(candle(i32::MAX as u32 + 100, -100).close(), i32::MAX);
+        assert_eq!(candle(i32::MAX as u32 + 100, -101).close(), i32::MAX - 1);
+    }
+
+    fn lottery(drawn: bool, winning_ticket: u64) -> Lottery {
+        Lottery {
+            disc: account_disc::LOTTERY,
+            bump: 255,
+            drawn: drawn as u8,
+            _padding: [0; 6],
+            authority: Pubkey::new_unique(),
+            pool: Pubkey::new_unique(),
+            lp_vault: Pubkey::new_unique(),
+            ticket_price: 1_000,
+            total_tickets: 0,
+            start_time: 0,
+            end_time: 0,
+            winner: Pubkey::default(),
+            winning_ticket,
+        }
+    }
+
+    fn entry(ticket_start: u64, ticket_count: u64) -> (Pubkey, LotteryEntry) {
+        let entry = LotteryEntry {
+            disc: account_disc::LOTENTRY,
+            bump: 255,
+            claimed: 0,
+            _padding: [0; 6],
+            owner: Pubkey::new_unique(),
+            lottery: Pubkey::new_unique(),
+            ticket_start,
+            ticket_count,
+        };
+        (Pubkey::new_unique(), entry)
+    }
+
+    #[test]
+    fn test_is_winner() {
+        let (_, e) = entry(100, 10);
+        assert!(!e.is_winner(99));
+        assert!(e.is_winner(100));
+        assert!(e.is_winner(109));
+        assert!(!e.is_winner(110));
+
+        let (_, empty) = entry(100, 0);
+        assert!(!empty.is_winner(100));
+
+        // `ticket_start + ticket_count` used to overflow here
+        let (_, top) = entry(u64::MAX - 4, 10);
+        assert!(!top.is_winner(u64::MAX - 5));
+        assert!(top.is_winner(u64::MAX - 4));
+        assert!(top.is_winner(u64::MAX));
+        let (_, all) = entry(0, u64::MAX);
+        assert!(all.is_winner(u64::MAX - 1));
+        assert!(!all.is_winner(u64::MAX));
+    }
+
+    #[test]
+    fn test_find_winning_entry() {
+        let entries = [entry(0, 10), entry(10, 5), entry(15, 100)];
+
+        // First and last ticket of the middle range
+        for ticket in [10, 14] {
+            let found = lottery(true, ticket).find_winning_entry(&entries);
+            assert_eq!(found.map(|(key, _)| *key), Some(entries[1].0));
+        }
+        let found = lottery(true, 15).find_winning_entry(&entries);
+        assert_eq!(found.map(|(key, _)| *key), Some(entries[2].0));
+
+        // Past every range, before the draw, or with nothing to search
+        assert!(lottery(true, 115).find_winning_entry(&entries).is_none());
+        assert!(lottery(false, 10).find_winning_entry(&entries).is_none());
+        assert!(lottery(true, 10).find_winning_entry(&[]).is_none());
+
+        // Claimed and foreign accounts never win
+        let mut claimed = entries.clone();
+        claimed[1].1.claimed = 1;
+        assert!(lottery(true, 10).find_winning_entry(&claimed).is_none());
+        let mut foreign = entries.clone();
+        foreign[1].1.disc = account_disc::LOTTERY;
+        assert!(lottery(true, 10).find_winning_entry(&foreign).is_none());
+
+        // A draw near u64::MAX
+        let top = [entry(0, 10), entry(u64::MAX - 9, 10)];
+        let found = lottery(true, u64::MAX).find_winning_entry(&top);
+        assert_eq!(found.map(|(key, _)| *key), Some(top[1].0));
+    }
+
+    /// Registry account bytes laid out field by field
+    fn registry_data(count: u32, pools: &[Pubkey]) -> Vec<u8> {
+        let mut data = Vec::with_capacity(REGISTRY_SIZE);
+        data.extend_from_slice(&account_disc::REGISTRY);
+        data.push(254); // bump
+        data.extend_from_slice(&[0; 7]);
+        data.extend_from_slice(&[1; 32]); // authority
+        data.extend_from_slice(&[0; 32]); // pending_auth
+        data.extend_from_slice(&0i64.to_le_bytes());
+        data.extend_from_slice(&count.to_le_bytes());
+        data.extend_from_slice(&[0; 4]);
+        for pool in pools {
+            data.extend_from_slice(pool.as_ref());
+        }
+        data.resize(REGISTRY_SIZE, 0);
+        data
+    }
+
+    #[test]
+    fn test_registry_layout() {
+        let pools: Vec<Pubkey> = (0..3).map(|_| Pubkey::new_unique()).collect();
+        let data = registry_data(3, &pools);
+        let registry = Registry::try_from_slice(&data).unwrap();
+
+        assert!(registry.is_valid());
+        assert_eq!(registry.bump, 254);
+        assert_eq!(registry.authority, Pubkey::new_from_array([1; 32]));
+        assert_eq!(registry.count, 3);
+        assert_eq!(borsh::to_vec(&registry).unwrap(), data);
+        assert_eq!(registry.registered_pools(), &pools[..]);
+        assert!(registry.contains(&pools[2]));
+        assert!(!registry.contains(&Pubkey::new_unique()));
+        // Unused slots are zeroed but not registered
+        assert!(!registry.contains(&Pubkey::default()));
+
+        // One byte short of the layout
+        assert!(Registry::try_from_slice(&data[..REGISTRY_SIZE - 1]).is_err());
+    }
+
+    #[test]
+    fn test_registry_count_bounds() {
+        let empty = Registry::try_from_slice(&registry_data(0, &[])).unwrap();
+        assert!(empty.registered_pools().is_empty());
+        assert!(!empty.contains(&Pubkey::default()));
+
+        // A count above capacity is clamped to the pools array
+        let pools: Vec<Pubkey> = (0..MAX_REGISTRY_POOLS)
+            .map(|_| Pubkey::new_unique())
+            .collect();
+        for count in [MAX_REGISTRY_POOLS as u32 + 1, u32::MAX] {
+            let full = Registry::try_from_slice(&registry_data(count, &pools)).unwrap();
+            assert_eq!(full.registered_pools(), &pools[..]);
+            assert!(full.contains(&pools[MAX_REGISTRY_POOLS - 1]));
+        }
+    }
+}
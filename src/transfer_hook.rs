@@ -0,0 +1,400 @@
+//! Token-2022 transfer hook account resolution
+//!
+//! Transfers of a mint with a transfer hook must carry the hook program,
+//! its validation account and every extra account listed there. These
+//! helpers read the validation account's `ExtraAccountMetaList` and append
+//! the resolved accounts to swap/liquidity instructions.
+
+#![allow(clippy::too_many_arguments)]
+
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    program_error::ProgramError,
+    pubkey::{Pubkey, MAX_SEEDS, MAX_SEED_LEN},
+};
+
+use crate::constants::disc;
+
+/// Seed prefix of the validation account: `["extra-account-metas", mint]`
+pub const EXTRA_ACCOUNT_METAS_SEED: &[u8] = b"extra-account-metas";
+
+/// Size of one packed `ExtraAccountMeta`
+const EXTRA_ACCOUNT_META_LEN: usize = 35;
+
+/// Discriminators >= this refer to the program at account index `disc - 128`
+const EXTERNAL_PDA_FLAG: u8 = 1 << 7;
+
+/// Find the validation account holding a mint's extra account metas
+pub fn find_extra_account_metas_address(mint: &Pubkey, hook_program: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[EXTRA_ACCOUNT_METAS_SEED, mint.as_ref()], hook_program)
+}
+
+/// One extra account required by a transfer hook, as stored on-chain
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExtraAccountMeta {
+    /// 0 = fixed address, 1 = PDA of the hook program,
+    /// 128 + i = PDA of the program at account index i
+    pub discriminator: u8,
+    /// Either the address itself or packed seed configs
+    pub address_config: [u8; 32],
+    pub is_signer: bool,
+    pub is_writable: bool,
+}
+
+impl ExtraAccountMeta {
+    fn unpack(bytes: &[u8]) -> Result<Self, ProgramError> {
+        if bytes.len() != EXTRA_ACCOUNT_META_LEN {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        let mut address_config = [0u8; 32];
+        address_config.copy_from_slice(&bytes[1..33]);
+
+        Ok(Self {
+            discriminator: bytes[0],
+            address_config,
+            is_signer: bytes[33] != 0,
+            is_writable: bytes[34] != 0,
+        })
+    }
+
+    /// Resolve to an `AccountMeta` given the execute instruction's
+    /// accounts and data resolved so far
+    fn resolve<F>(
+        &self,
+        hook_program: &Pubkey,
+        accounts: &[Pubkey],
+        ix_data: &[u8],
+        account_data: &F,
+    ) -> Result<AccountMeta, ProgramError>
+    where
+        F: Fn(&Pubkey) -> Option<Vec<u8>>,
+    {
+        let pubkey = match self.discriminator {
+            0 => Pubkey::new_from_array(self.address_config),
+            1 => resolve_pda(
+                &self.address_config,
+                hook_program,
+                accounts,
+                ix_data,
+                account_data,
+            )?,
+            d if d >= EXTERNAL_PDA_FLAG => {
+                let program = accounts
+                    .get((d - EXTERNAL_PDA_FLAG) as usize)
+                    .ok_or(ProgramError::NotEnoughAccountKeys)?;
+                resolve_pda(
+                    &self.address_config,
+                    program,
+                    accounts,
+                    ix_data,
+                    account_data,
+                )?
+            }
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+
+        Ok(AccountMeta {
+            pubkey,
+            is_signer: self.is_signer,
+            is_writable: self.is_writable,
+        })
+    }
+}
+
+/// Derive a PDA from packed seed configs:
+/// 1 = literal, 2 = instruction data, 3 = account key, 4 = account data.
+///
+/// Seeds come from untrusted account data, so a seed over `MAX_SEED_LEN`
+/// bytes or more seeds than fit beside the bump fail with `InvalidSeeds`.
+fn resolve_pda<F>(
+    config: &[u8; 32],
+    program_id: &Pubkey,
+    accounts: &[Pubkey],
+    ix_data: &[u8],
+    account_data: &F,
+) -> Result<Pubkey, ProgramError>
+where
+    F: Fn(&Pubkey) -> Option<Vec<u8>>,
+{
+    let mut seeds: Vec<Vec<u8>> = Vec::new();
+    let mut i = 0;
+
+    let byte = |at: usize| {
+        config
+            .get(at)
+            .copied()
+            .ok_or(ProgramError::InvalidAccountData)
+    };
+    let slice = |data: &[u8], start: u8, len: u8| -> Result<Vec<u8>, ProgramError> {
+        let start = start as usize;
+        let end = start + len as usize;
+        data.get(start..end)
+            .map(|s| s.to_vec())
+            .ok_or(ProgramError::InvalidAccountData)
+    };
+
+    while i < config.len() {
+        match config[i] {
+            0 => break,
+            1 => {
+                let len = byte(i + 1)? as usize;
+                let seed = config
+                    .get(i + 2..i + 2 + len)
+                    .ok_or(ProgramError::InvalidAccountData)?;
+                seeds.push(seed.to_vec());
+                i += 2 + len;
+            }
+            2 => {
+                seeds.push(slice(ix_data, byte(i + 1)?, byte(i + 2)?)?);
+                i += 3;
+            }
+            3 => {
+                let key = accounts
+                    .get(byte(i + 1)? as usize)
+                    .ok_or(ProgramError::NotEnoughAccountKeys)?;
+                seeds.push(key.to_bytes().to_vec());
+                i += 2;
+            }
+            4 => {
+                let key = accounts
+                    .get(byte(i + 1)? as usize)
+                    .ok_or(ProgramError::NotEnoughAccountKeys)?;
+                let data = account_data(key).ok_or(ProgramError::UninitializedAccount)?;
+                seeds.push(slice(&data, byte(i + 2)?, byte(i + 3)?)?);
+                i += 4;
+            }
+            _ => return Err(ProgramError::InvalidAccountData),
+        }
+    }
+
+    if seeds.len() >= MAX_SEEDS || seeds.iter().any(|s| s.len() > MAX_SEED_LEN) {
+        return Err(ProgramError::InvalidSeeds);
+    }
+
+    let seed_refs: Vec<&[u8]> = seeds.iter().map(|s| s.as_slice()).collect();
+    Pubkey::try_find_program_address(&seed_refs, program_id)
+        .map(|(pda, _)| pda)
+        .ok_or(ProgramError::InvalidSeeds)
+}
+
+/// Parse the execute-instruction entry of a validation account's TLV data
+pub fn parse_extra_account_metas(data: &[u8]) -> Result<Vec<ExtraAccountMeta>, ProgramError> {
+    let exec_type = disc::TH_EXEC.to_le_bytes();
+    let mut offset = 0;
+
+    while offset + 12 <= data.len() {
+        let entry_type = &data[offset..offset + 8];
+        let len = u32::from_le_bytes(data[offset + 8..offset + 12].try_into().unwrap()) as usize;
+        let value = data
+            .get(offset + 12..offset + 12 + len)
+            .ok_or(ProgramError::InvalidAccountData)?;
+
+        if entry_type == exec_type {
+            // PodSlice: u32 count followed by packed metas
+            let count_bytes = value.get(..4).ok_or(ProgramError::InvalidAccountData)?;
+            let count = u32::from_le_bytes(count_bytes.try_into().unwrap()) as usize;
+            let metas = count
+                .checked_mul(EXTRA_ACCOUNT_META_LEN)
+                .and_then(|end| value.get(4..4 + end))
+                .ok_or(ProgramError::InvalidAccountData)?;
+
+            return metas
+                .chunks_exact(EXTRA_ACCOUNT_META_LEN)
+                .map(ExtraAccountMeta::unpack)
+                .collect();
+        }
+
+        offset += 12 + len;
+    }
+
+    Err(ProgramError::InvalidAccountData)
+}
+
+/// Resolve the accounts a hooked transfer needs.
+///
+/// Returns the extra accounts followed by the hook program and the
+/// validation account, in the order Token-2022 expects them after the
+/// transfer's own accounts. `account_data` looks up account contents for
+/// seeds that read account data; pass `|_| None` if the list uses none.
+pub fn resolve_transfer_hook_accounts<F>(
+    hook_program: &Pubkey,
+    source: &Pubkey,
+    mint: &Pubkey,
+    destination: &Pubkey,
+    owner: &Pubkey,
+    amount: u64,
+    meta_list_data: &[u8],
+    account_data: F,
+) -> Result<Vec<AccountMeta>, ProgramError>
+where
+    F: Fn(&Pubkey) -> Option<Vec<u8>>,
+{
+    let (validation, _) = find_extra_account_metas_address(mint, hook_program);
+    let metas = parse_extra_account_metas(meta_list_data)?;
+
+    // Accounts and data of the hook's execute instruction
+    let mut accounts = vec![*source, *mint, *destination, *owner, validation];
+    let mut ix_data = disc::TH_EXEC.to_le_bytes().to_vec();
+    ix_data.extend_from_slice(&amount.to_le_bytes());
+
+    let mut resolved = Vec::with_capacity(metas.len() + 2);
+    for meta in &metas {
+        let account = meta.resolve(hook_program, &accounts, &ix_data, &account_data)?;
+        accounts.push(account.pubkey);
+        resolved.push(account);
+    }
+
+    resolved.push(AccountMeta::new_readonly(*hook_program, false));
+    resolved.push(AccountMeta::new_readonly(validation, false));
+
+    Ok(resolved)
+}
+
+/// Append the accounts of a hooked transfer to `ix` (see
+/// `resolve_transfer_hook_accounts`)
+pub fn add_transfer_hook_accounts<F>(
+    ix: &mut Instruction,
+    hook_program: &Pubkey,
+    source: &Pubkey,
+    mint: &Pubkey,
+    destination: &Pubkey,
+    owner: &Pubkey,
+    amount: u64,
+    meta_list_data: &[u8],
+    account_data: F,
+) -> Result<(), ProgramError>
+where
+    F: Fn(&Pubkey) -> Option<Vec<u8>>,
+{
+    let extra = resolve_transfer_hook_accounts(
+        hook_program,
+        source,
+        mint,
+        destination,
+        owner,
+        amount,
+        meta_list_data,
+        account_data,
+    )?;
+    ix.accounts.extend(extra);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pack_metas(metas: &[ExtraAccountMeta]) -> Vec<u8> {
+        let mut value = (metas.len() as u32).to_le_bytes().to_vec();
+        for meta in metas {
+            value.push(meta.discriminator);
+            value.extend_from_slice(&meta.address_config);
+            value.push(meta.is_signer as u8);
+            value.push(meta.is_writable as u8);
+        }
+
+        let mut data = disc::TH_EXEC.to_le_bytes().to_vec();
+        data.extend_from_slice(&(value.len() as u32).to_le_bytes());
+        data.extend_from_slice(&value);
+        data
+    }
+
+    #[test]
+    fn test_resolve_transfer_hook_accounts() {
+        let hook = Pubkey::new_unique();
+        let (source, mint, dest, owner) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let fixed = Pubkey::new_unique();
+
+        // PDA seeds: literal "cfg", mint (account 1), amount (data 8..16)
+        let mut config = [0u8; 32];
+        config[..5].copy_from_slice(&[1, 3, b'c', b'f', b'g']);
+        config[5..7].copy_from_slice(&[3, 1]);
+        config[7..10].copy_from_slice(&[2, 8, 8]);
+
+        let data = pack_metas(&[
+            ExtraAccountMeta {
+                discriminator: 0,
+                address_config: fixed.to_bytes(),
+                is_signer: false,
+                is_writable: true,
+            },
+            ExtraAccountMeta {
+                discriminator: 1,
+                address_config: config,
+                is_signer: false,
+                is_writable: false,
+            },
+        ]);
+
+        let amount = 42u64;
+        let metas = resolve_transfer_hook_accounts(
+            &hook,
+            &source,
+            &mint,
+            &dest,
+            &owner,
+            amount,
+            &data,
+            |_| None,
+        )
+        .unwrap();
+
+        let (pda, _) =
+            Pubkey::find_program_address(&[b"cfg", mint.as_ref(), &amount.to_le_bytes()], &hook);
+        let (validation, _) = find_extra_account_metas_address(&mint, &hook);
+
+        assert_eq!(metas.len(), 4);
+        assert_eq!(metas[0], AccountMeta::new(fixed, false));
+        assert_eq!(metas[1], AccountMeta::new_readonly(pda, false));
+        assert_eq!(metas[2], AccountMeta::new_readonly(hook, false));
+        assert_eq!(metas[3], AccountMeta::new_readonly(validation, false));
+    }
+
+    #[test]
+    fn test_resolve_rejects_invalid_seeds() {
+        let hook = Pubkey::new_unique();
+        let keys = [
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        ];
+        let resolve = |config: [u8; 32], account_data: &dyn Fn(&Pubkey) -> Option<Vec<u8>>| {
+            let data = pack_metas(&[ExtraAccountMeta {
+                discriminator: 1,
+                address_config: config,
+                is_signer: false,
+                is_writable: false,
+            }]);
+            resolve_transfer_hook_accounts(
+                &hook,
+                &keys[0],
+                &keys[1],
+                &keys[2],
+                &keys[0],
+                1,
+                &data,
+                account_data,
+            )
+        };
+
+        // 33 bytes of the mint's account data: one byte over MAX_SEED_LEN
+        let mut config = [0u8; 32];
+        config[..4].copy_from_slice(&[4, 1, 0, 33]);
+        let long_data = |_: &Pubkey| Some(vec![7u8; 64]);
+        assert_eq!(resolve(config, &long_data), Err(ProgramError::InvalidSeeds));
+
+        // Exactly MAX_SEED_LEN bytes still resolves
+        config[3] = 32;
+        assert!(resolve(config, &long_data).is_ok());
+
+        // Sixteen account-key seeds leave no room for the bump
+        let config = [3u8, 0].repeat(16).try_into().unwrap();
+        assert_eq!(resolve(config, &|_| None), Err(ProgramError::InvalidSeeds));
+    }
+}